@@ -7,15 +7,27 @@ use tokio::sync::mpsc::{Sender, channel};
 
 pub use self::futures::Futures;
 pub use self::handle::Handle;
+#[cfg(feature = "ezsp")]
+pub use self::handle::WeakHandle;
+pub use self::message::Message;
 pub use self::receiver::Receiver;
+pub use self::role::Role;
 pub use self::transmitter::Transmitter;
+pub use self::transport::TransportFactory;
+use self::transport::{Reopener, Reopening};
+use crate::observer::{Observer, Tap};
+use crate::stats::Counters;
 use crate::types::Payload;
 
+#[cfg(feature = "tracing")]
+mod fields;
 mod futures;
 mod handle;
 mod message;
 mod receiver;
+mod role;
 mod transmitter;
+mod transport;
 
 /// Create the `ASHv2` actor futures for the given asynchronous reader and writer.
 ///
@@ -37,18 +49,168 @@ pub fn start<R, W>(
     Handle,
     Futures<impl Future<Output = ()> + Send + 'static, impl Future<Output = ()> + Send + 'static>,
 )
+where
+    R: AsyncRead + Send + Sync + Unpin + 'static,
+    W: AsyncWrite + Send + Sync + Unpin + 'static,
+{
+    create(reader, writer, response, Role::Host, Tap::default(), None)
+}
+
+/// Create the `ASHv2` actor futures like [`start`] and notify `observer` of every frame.
+///
+/// The observer is invoked with every frame the actor writes to or reads from the transport,
+/// including received frames that fail CRC validation or parsing.
+pub fn start_with_observer<R, W, O>(
+    reader: R,
+    writer: W,
+    response: Sender<Payload>,
+    observer: O,
+) -> (
+    Handle,
+    Futures<impl Future<Output = ()> + Send + 'static, impl Future<Output = ()> + Send + 'static>,
+)
+where
+    R: AsyncRead + Send + Sync + Unpin + 'static,
+    W: AsyncWrite + Send + Sync + Unpin + 'static,
+    O: Observer,
+{
+    create(
+        reader,
+        writer,
+        response,
+        Role::Host,
+        Tap::new(observer),
+        None,
+    )
+}
+
+/// Create the `ASHv2` actor futures like [`start`] on a transport opened by `factory`.
+///
+/// Whenever reading from or writing to the transport fails fatally, the actor calls the factory
+/// again to reopen it, retrying every second, and re-runs the `RST` handshake on the new
+/// transport. Existing [`Handle`] clones and the response channel stay valid across reopens.
+/// Payloads sent while the connection is being reset are sent once it has been re-established.
+///
+/// # Errors
+///
+/// Returns an error if the factory fails to open the initial transport.
+pub async fn start_with_factory<F>(
+    factory: F,
+    response: Sender<Payload>,
+) -> std::io::Result<(
+    Handle,
+    Futures<impl Future<Output = ()> + Send + 'static, impl Future<Output = ()> + Send + 'static>,
+)>
+where
+    F: TransportFactory,
+{
+    start_with_factory_and_tap(factory, response, Tap::default()).await
+}
+
+/// Create the `ASHv2` actor futures like [`start_with_factory`] and notify `observer` of every
+/// frame like [`start_with_observer`].
+///
+/// # Errors
+///
+/// Returns an error if the factory fails to open the initial transport.
+pub async fn start_with_factory_and_observer<F, O>(
+    factory: F,
+    response: Sender<Payload>,
+    observer: O,
+) -> std::io::Result<(
+    Handle,
+    Futures<impl Future<Output = ()> + Send + 'static, impl Future<Output = ()> + Send + 'static>,
+)>
+where
+    F: TransportFactory,
+    O: Observer,
+{
+    start_with_factory_and_tap(factory, response, Tap::new(observer)).await
+}
+
+/// Open the initial transport with `factory` and create the host actor futures with `tap`.
+async fn start_with_factory_and_tap<F>(
+    mut factory: F,
+    response: Sender<Payload>,
+    tap: Tap,
+) -> std::io::Result<(
+    Handle,
+    Futures<impl Future<Output = ()> + Send + 'static, impl Future<Output = ()> + Send + 'static>,
+)>
+where
+    F: TransportFactory,
+{
+    let (reader, writer) = factory.open().await?;
+    Ok(create(
+        reader,
+        writer,
+        response,
+        Role::Host,
+        tap,
+        Some(Reopener::reopening(factory)),
+    ))
+}
+
+/// Create the actor futures for the given role and return a handle to their message queue.
+pub fn create<R, W>(
+    reader: R,
+    writer: W,
+    response: Sender<Payload>,
+    role: Role,
+    tap: Tap,
+    reopening: Option<Reopening<R, W>>,
+) -> (
+    Handle,
+    Futures<impl Future<Output = ()> + Send + 'static, impl Future<Output = ()> + Send + 'static>,
+)
 where
     R: AsyncRead + Send + Sync + Unpin + 'static,
     W: AsyncWrite + Send + Sync + Unpin + 'static,
 {
     let (sender, inbox) = channel(response.capacity());
     let running = Arc::new(AtomicBool::new(true));
-    let receiver = Receiver::new(reader, response, sender.clone()).run(running.clone());
-    let transmitter = Transmitter::new(writer, inbox, sender.downgrade()).run(running);
+    let counters = Arc::new(Counters::new());
+    let mut receiver = Receiver::new(
+        reader,
+        response,
+        sender.clone(),
+        counters.clone(),
+        tap.clone(),
+    );
+    let mut transmitter = Transmitter::new(
+        writer,
+        inbox,
+        sender.downgrade(),
+        role,
+        counters.clone(),
+        tap,
+    );
+
+    if let Some(Reopening { readers, reopener }) = reopening {
+        receiver = receiver.with_readers(readers);
+        transmitter = transmitter.with_reopener(reopener);
+    }
+
+    let receiver = receiver.run(running.clone());
+    let transmitter = transmitter.run(running);
+    #[cfg(feature = "tracing")]
+    let (transmitter, receiver) = {
+        use tracing::Instrument;
+
+        let span = tracing::info_span!(
+            "ashv2",
+            connection = counters.connection_id(),
+            role = ?role
+        );
+        (
+            transmitter.instrument(span.clone()),
+            receiver.instrument(span),
+        )
+    };
     let futures = Futures {
         transmitter,
         receiver,
     };
 
-    (sender.into(), futures)
+    (Handle::new(sender, counters), futures)
 }
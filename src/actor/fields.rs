@@ -0,0 +1,126 @@
+//! Structured frame fields for `tracing` events.
+
+use crate::frame::{Ack, Data, Frame, Nak};
+use crate::observer::Direction;
+
+/// Structured fields describing a frame.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FrameFields {
+    frame_type: &'static str,
+    frame_num: Option<u8>,
+    ack_num: Option<u8>,
+    retransmit: Option<bool>,
+    payload_len: usize,
+}
+
+impl FrameFields {
+    /// Return `true` if a subscriber is interested in frame events.
+    pub fn enabled() -> bool {
+        tracing::enabled!(tracing::Level::DEBUG)
+    }
+
+    /// Emit a `tracing` event with the frame's fields.
+    pub fn trace(self, direction: Direction) {
+        let direction = match direction {
+            Direction::Sent => "sent",
+            Direction::Received => "received",
+        };
+
+        tracing::debug!(
+            direction,
+            frame_type = self.frame_type,
+            frame_num = self.frame_num,
+            ack_num = self.ack_num,
+            retransmit = self.retransmit,
+            payload_len = self.payload_len,
+            "ASHv2 frame {direction}"
+        );
+    }
+
+    const fn control(frame_type: &'static str) -> Self {
+        Self {
+            frame_type,
+            frame_num: None,
+            ack_num: None,
+            retransmit: None,
+            payload_len: 0,
+        }
+    }
+}
+
+impl From<&Frame> for FrameFields {
+    fn from(frame: &Frame) -> Self {
+        match frame {
+            Frame::Ack(ack) => ack.into(),
+            Frame::Data(data) => data.into(),
+            Frame::Error(_) => Self::control("ERROR"),
+            Frame::Nak(nak) => nak.into(),
+            Frame::Rst(_) => Self::control("RST"),
+            Frame::RstAck(_) => Self::control("RST_ACK"),
+        }
+    }
+}
+
+impl From<&Ack> for FrameFields {
+    fn from(ack: &Ack) -> Self {
+        Self {
+            ack_num: Some(ack.ack_num()),
+            ..Self::control("ACK")
+        }
+    }
+}
+
+impl From<&Data> for FrameFields {
+    fn from(data: &Data) -> Self {
+        Self {
+            frame_type: "DATA",
+            frame_num: Some(data.frame_num()),
+            ack_num: Some(data.ack_num()),
+            retransmit: Some(data.is_retransmission()),
+            payload_len: data.payload_len(),
+        }
+    }
+}
+
+impl From<&Nak> for FrameFields {
+    fn from(nak: &Nak) -> Self {
+        Self {
+            ack_num: Some(nak.ack_num()),
+            ..Self::control("NAK")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameFields;
+    use crate::frame::{Ack, Data, Frame, Nak, RST};
+
+    #[test]
+    fn data_fields() {
+        let data = Data::new(5, 2, [0x00, 0x00, 0x00, 0x02].into_iter().collect());
+        let fields = FrameFields::from(&data);
+        assert_eq!(fields.frame_type, "DATA");
+        assert_eq!(fields.frame_num, Some(5));
+        assert_eq!(fields.ack_num, Some(2));
+        assert_eq!(fields.retransmit, Some(false));
+        assert_eq!(fields.payload_len, 4);
+    }
+
+    #[test]
+    fn control_frame_fields() {
+        let fields = FrameFields::from(&Frame::Ack(Ack::new(3, false)));
+        assert_eq!(fields.frame_type, "ACK");
+        assert_eq!(fields.ack_num, Some(3));
+
+        let fields = FrameFields::from(&Frame::Nak(Nak::new(6, true)));
+        assert_eq!(fields.frame_type, "NAK");
+        assert_eq!(fields.ack_num, Some(6));
+
+        let fields = FrameFields::from(&Frame::Rst(RST));
+        assert_eq!(fields.frame_type, "RST");
+        assert_eq!(fields.frame_num, None);
+        assert_eq!(fields.ack_num, None);
+        assert_eq!(fields.payload_len, 0);
+    }
+}
@@ -1,12 +1,19 @@
-use std::io;
+use std::sync::Arc;
+#[cfg(feature = "ezsp")]
+use std::sync::Weak;
 
 use log::trace;
 use tokio::sync::mpsc::Sender;
-use tokio::sync::oneshot::channel;
+#[cfg(feature = "ezsp")]
+use tokio::sync::mpsc::WeakSender;
+use tokio::sync::oneshot::{Receiver, channel};
+use tokio::sync::watch;
 
 use crate::Payload;
 use crate::actor::message::Message;
+use crate::error::Error;
 use crate::hex_slice::HexSlice;
+use crate::stats::{Counters, Stats};
 
 /// User-facing handle for sending payloads to the `ASHv2` actor.
 ///
@@ -16,6 +23,7 @@ use crate::hex_slice::HexSlice;
 #[derive(Clone, Debug)]
 pub struct Handle {
     inner: Sender<Message>,
+    counters: Arc<Counters>,
 }
 
 impl Handle {
@@ -23,26 +31,97 @@ impl Handle {
     ///
     /// # Errors
     ///
-    /// Returns an error if the actor futures are no longer accepting messages or if the
-    /// transmitter fails to write the payload.
-    pub async fn send(&self, payload: Payload) -> io::Result<()> {
+    /// Returns [`Error::ActorShutDown`] if the actor futures are no longer accepting messages, or
+    /// another [`Error`] if the transmitter failed to write the payload.
+    pub async fn send(&self, payload: Payload) -> Result<(), Error> {
+        self.send_in_connection(payload).await.map(drop)
+    }
+
+    /// Return a snapshot of the link statistics.
+    ///
+    /// The statistics remain available after the actor futures have terminated.
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        self.counters.snapshot()
+    }
+
+    /// Subscribe to the number of established connections.
+    ///
+    /// The value is incremented each time the actor establishes the connection, i.e. when the
+    /// host receives a valid `RST_ACK` frame or the NCP acknowledges an `RST` frame. Any change
+    /// after the initial connection therefore signals that the NCP has been reset.
+    #[must_use]
+    pub fn connections(&self) -> watch::Receiver<u64> {
+        self.counters.connections()
+    }
+
+    /// Create a reference to the actor that does not keep its message queue open.
+    #[cfg(feature = "ezsp")]
+    pub(crate) fn downgrade(&self) -> WeakHandle {
+        WeakHandle {
+            inner: self.inner.downgrade(),
+            counters: Arc::downgrade(&self.counters),
+        }
+    }
+
+    /// Send data and return the number of the connection during which it has been sent.
+    ///
+    /// The number is the value of [`connections`](Self::connections) at the time of sending.
+    pub(crate) async fn send_in_connection(&self, payload: Payload) -> Result<u64, Error> {
+        self.submit(payload)
+            .await?
+            .await
+            .map_err(|_| Error::ActorShutDown)?
+    }
+
+    /// Enqueue a payload and return the receiver of its transmission result.
+    pub(crate) async fn submit(
+        &self,
+        payload: Payload,
+    ) -> Result<Receiver<Result<u64, Error>>, Error> {
         let (response_tx, response_rx) = channel();
 
         trace!("Sending chunk: {:#04X}", HexSlice::new(&payload));
+        self.enqueue(Message::Payload {
+            payload: Box::new(payload),
+            response_tx,
+        })
+        .await?;
+
+        Ok(response_rx)
+    }
+
+    /// Enqueue a message for the transmitter.
+    pub(crate) async fn enqueue(&self, message: Message) -> Result<(), Error> {
         self.inner
-            .send(Message::Payload {
-                payload: Box::new(payload),
-                response_tx,
-            })
+            .send(message)
             .await
-            .map_err(io::Error::other)?;
+            .map_err(|_| Error::ActorShutDown)
+    }
+}
 
-        response_rx.await.map_err(io::Error::other)?
+impl Handle {
+    /// Create a handle that sends messages through the given sender.
+    pub(crate) const fn new(inner: Sender<Message>, counters: Arc<Counters>) -> Self {
+        Self { inner, counters }
     }
 }
 
-impl From<Sender<Message>> for Handle {
-    fn from(inner: Sender<Message>) -> Self {
-        Self { inner }
+/// Reference to the `ASHv2` actor that does not keep its message queue open.
+#[cfg(feature = "ezsp")]
+#[derive(Debug)]
+pub struct WeakHandle {
+    inner: WeakSender<Message>,
+    counters: Weak<Counters>,
+}
+
+#[cfg(feature = "ezsp")]
+impl WeakHandle {
+    /// Return a handle, unless every handle has been dropped or the actor has terminated.
+    pub fn upgrade(&self) -> Option<Handle> {
+        Some(Handle {
+            inner: self.inner.upgrade()?,
+            counters: self.counters.upgrade()?,
+        })
     }
 }
@@ -1,22 +1,23 @@
 use std::fmt::Display;
-use std::io;
 
 use tokio::sync::oneshot::Sender;
 
 use crate::Payload;
+use crate::code::Code;
+use crate::error::Error as ActorError;
 use crate::frame::{Error, Rst, RstAck};
 use crate::hex_slice::HexSlice;
 
 /// Messages sent to the `ASHv2` transmitter.
 #[derive(Debug)]
-#[cfg_attr(target_pointer_width = "64", expect(variant_size_differences))]
 pub enum Message {
     /// Payload received from the network.
     Payload {
         /// Data payload to send.
         payload: Box<Payload>,
-        /// Response channel to notify when the payload has been sent.
-        response_tx: Sender<io::Result<()>>,
+        /// Response channel to notify with the number of the connection during which the payload
+        /// has been sent.
+        response_tx: Sender<Result<u64, ActorError>>,
     },
 
     /// Send an ACK frame with the given ack number.
@@ -25,6 +26,9 @@ pub enum Message {
     /// Send a NAK frame with the given ack number.
     Nak(u8),
 
+    /// Send an ERROR frame with the given error code and enter the failed state.
+    SendError(Code),
+
     /// Received RST frame.
     Rst(Rst),
 
@@ -39,6 +43,9 @@ pub enum Message {
 
     /// Negative Acknowledgement sent frames up to the given frame number.
     NakSentFrame(u8),
+
+    /// The receiver's transport of the given generation failed and has to be reopened.
+    Reopen(u64),
 }
 
 impl Display for Message {
@@ -47,11 +54,13 @@ impl Display for Message {
             Self::Payload { payload, .. } => write!(f, "Payload({:#04X})", HexSlice::new(payload)),
             Self::Ack(ack_num) => write!(f, "Ack({ack_num})"),
             Self::Nak(ack_num) => write!(f, "Nak({ack_num})"),
+            Self::SendError(code) => write!(f, "SendError({code})"),
             Self::Rst(rst) => write!(f, "Rst({rst})"),
             Self::RstAck(rst_ack) => write!(f, "RstAck({rst_ack})"),
             Self::Error(error) => write!(f, "Error({error})"),
             Self::AckSentFrame(ack_num) => write!(f, "AckSentFrame({ack_num})"),
             Self::NakSentFrame(ack_num) => write!(f, "NakSentFrame({ack_num})"),
+            Self::Reopen(generation) => write!(f, "Reopen({generation})"),
         }
     }
 }
@@ -1,18 +1,28 @@
+use std::future::poll_fn;
+use std::io::{self, ErrorKind};
 use std::ops::BitAnd;
+use std::pin::pin;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
+use std::task::Poll;
 
 use log::{debug, error, info, trace, warn};
 use tokio::io::AsyncRead;
-use tokio::sync::mpsc::Sender;
 use tokio::sync::mpsc::error::SendError;
+use tokio::sync::mpsc::{Sender, UnboundedReceiver};
 
 use self::buffer::Buffer;
 use crate::SEQ_MASK;
+#[cfg(feature = "tracing")]
+use crate::actor::fields::FrameFields;
 use crate::actor::message::Message;
 use crate::frame::{Ack, Data, Error, Frame, Nak, Rst, RstAck};
+#[cfg(feature = "tracing")]
+use crate::observer::Direction;
+use crate::observer::Tap;
 use crate::protocol::Mask;
+use crate::stats::Counters;
 use crate::types::{MAX_FRAME_SIZE, Payload};
 use crate::validate::Validate;
 
@@ -25,6 +35,9 @@ pub struct Receiver<R> {
     response: Sender<Payload>,
     transmitter: Sender<Message>,
     last_received_frame_num: Option<u8>,
+    counters: Arc<Counters>,
+    readers: Option<UnboundedReceiver<R>>,
+    generation: u64,
 }
 
 impl<R> Receiver<R>
@@ -32,14 +45,38 @@ where
     R: AsyncRead,
 {
     /// Creates a new `ASHv2` receiver.
-    pub fn new(reader: R, response: Sender<Payload>, transmitter: Sender<Message>) -> Self {
+    pub fn new(
+        reader: R,
+        response: Sender<Payload>,
+        transmitter: Sender<Message>,
+        counters: Arc<Counters>,
+        tap: Tap,
+    ) -> Self {
         Self {
-            buffer: Buffer::new(reader),
+            buffer: Buffer::new(reader, counters.clone(), tap),
             response,
             transmitter,
             last_received_frame_num: None,
+            counters,
+            readers: None,
+            generation: 0,
         }
     }
+
+    /// Read from the readers of reopened transports.
+    #[must_use]
+    pub fn with_readers(mut self, readers: UnboundedReceiver<R>) -> Self {
+        self.readers.replace(readers);
+        self
+    }
+
+    /// Continue reading from a reopened transport.
+    fn replace(&mut self, reader: R) {
+        debug!("Reading from reopened transport.");
+        self.buffer.replace(reader);
+        self.generation = self.generation.wrapping_add(1);
+        self.last_received_frame_num.take();
+    }
 }
 
 impl<R> Receiver<R>
@@ -51,10 +88,16 @@ where
         trace!("Starting receiver with frame size: {MAX_FRAME_SIZE}");
 
         while running.load(Relaxed) {
-            let maybe_frame = match self.buffer.read_frame().await {
+            let maybe_frame = match self.read_frame().await {
                 Ok(maybe_frame) => maybe_frame,
                 Err(error) => {
                     error!("Error receiving frame: {error}");
+
+                    if error.kind() != ErrorKind::InvalidData && !self.await_reopen().await {
+                        info!("Transport cannot be reopened, receiver exiting.");
+                        break;
+                    }
+
                     continue;
                 }
             };
@@ -72,6 +115,71 @@ where
         debug!("Receiver loop terminated.");
     }
 
+    /// Read the next frame, switching to a new reader if the transmitter reopened the transport.
+    async fn read_frame(&mut self) -> io::Result<Option<Frame>> {
+        let Some(readers) = self.readers.as_mut() else {
+            return self.buffer.read_frame().await;
+        };
+
+        let reader = {
+            let mut frame = pin!(self.buffer.read_frame());
+            let reader = poll_fn(|context| {
+                if let Poll::Ready(reader) = readers.poll_recv(context) {
+                    return Poll::Ready(Ok(reader));
+                }
+
+                frame.as_mut().poll(context).map(Err)
+            })
+            .await;
+
+            match reader {
+                Ok(reader) => reader,
+                Err(frame) => return frame,
+            }
+        };
+
+        if let Some(reader) = reader {
+            self.replace(reader);
+        } else {
+            debug!("Transmitter dropped the transport factory.");
+            self.readers.take();
+        }
+
+        Ok(None)
+    }
+
+    /// Ask the transmitter to reopen the failed transport and wait for its new reader.
+    ///
+    /// Returns `true` immediately if the actor has been started without a transport factory, and
+    /// `false` if the transmitter has terminated.
+    async fn await_reopen(&mut self) -> bool {
+        let Some(readers) = self.readers.as_mut() else {
+            return true;
+        };
+
+        if self
+            .transmitter
+            .send(Message::Reopen(self.generation))
+            .await
+            .is_err()
+        {
+            return false;
+        }
+
+        let Some(mut reader) = readers.recv().await else {
+            return false;
+        };
+
+        // Skip readers of transports that have been reopened in the meantime.
+        while let Ok(newer) = readers.try_recv() {
+            self.generation = self.generation.wrapping_add(1);
+            reader = newer;
+        }
+
+        self.replace(reader);
+        true
+    }
+
     /// Returns the ACK number.
     ///
     /// This is equal to the last received frame number plus one.
@@ -82,9 +190,12 @@ where
     }
 
     async fn handle_frame(&mut self, frame: Frame) -> Result<(), SendError<Message>> {
+        #[cfg(feature = "tracing")]
+        FrameFields::from(&frame).trace(Direction::Received);
+
         match frame {
             Frame::Ack(ack) => self.handle_ack(ack).await,
-            Frame::Data(data) => self.handle_data(*data).await,
+            Frame::Data(data) => self.handle_data(data).await,
             Frame::Error(error) => self.handle_error(error).await,
             Frame::Nak(nak) => self.handle_nak(nak).await,
             Frame::Rst(rst) => self.handle_rst(rst).await,
@@ -95,9 +206,11 @@ where
     /// Handle an incoming `ACK` frame.
     async fn handle_ack(&self, ack: Ack) -> Result<(), SendError<Message>> {
         if let Ok(ack) = ack.validate() {
+            self.counters.acks_received.increment();
             self.ack_sent_frames(ack.ack_num()).await
         } else {
             warn!("Received ACK with invalid CRC.");
+            self.counters.crc_failures.ack.increment();
             Ok(())
         }
     }
@@ -108,10 +221,13 @@ where
 
         let Ok(data) = data.validate() else {
             warn!("Received data frame with invalid CRC.");
+            self.counters.crc_failures.data.increment();
             self.send_nak().await?;
             return Ok(());
         };
 
+        self.counters.data_frames_received.increment();
+
         if data.frame_num() == self.ack_number() {
             trace!("Received in-sequence data frame: {data}");
             self.last_received_frame_num.replace(data.frame_num());
@@ -130,15 +246,18 @@ where
         }
 
         warn!("Received out-of-sequence data frame: {data}");
+        self.counters.out_of_sequence_frames.increment();
         self.send_nak().await?;
         Ok(())
     }
 
     async fn handle_error(&self, error: Error) -> Result<(), SendError<Message>> {
         if let Ok(error) = error.validate() {
+            self.counters.count_error(error.code());
             self.transmitter.send(Message::Error(error)).await
         } else {
             warn!("Received ERROR with invalid CRC.");
+            self.counters.crc_failures.error.increment();
             Ok(())
         }
     }
@@ -146,27 +265,34 @@ where
     /// Handle an incoming `NAK` frame.
     async fn handle_nak(&self, nak: Nak) -> Result<(), SendError<Message>> {
         if let Ok(nak) = nak.validate() {
+            self.counters.naks_received.increment();
             self.nak_sent_frames(nak.ack_num()).await
         } else {
             warn!("Received NAK with invalid CRC.");
+            self.counters.crc_failures.nak.increment();
             Ok(())
         }
     }
 
-    async fn handle_rst(&self, rst: Rst) -> Result<(), SendError<Message>> {
+    async fn handle_rst(&mut self, rst: Rst) -> Result<(), SendError<Message>> {
         if let Ok(rst) = rst.validate() {
+            self.last_received_frame_num.take();
             self.transmitter.send(Message::Rst(rst)).await
         } else {
             warn!("Received RST with invalid CRC.");
+            self.counters.crc_failures.rst.increment();
             Ok(())
         }
     }
 
-    async fn handle_rst_ack(&self, rst_ack: RstAck) -> Result<(), SendError<Message>> {
+    async fn handle_rst_ack(&mut self, rst_ack: RstAck) -> Result<(), SendError<Message>> {
         if let Ok(rst_ack) = rst_ack.validate() {
+            self.counters.count_reset(rst_ack.code());
+            self.last_received_frame_num.take();
             self.transmitter.send(Message::RstAck(rst_ack)).await
         } else {
             warn!("Received RST-ACK with invalid CRC.");
+            self.counters.crc_failures.rst_ack.increment();
             Ok(())
         }
     }
@@ -177,6 +303,9 @@ where
         self.response.send(payload).await.unwrap_or_else(|error| {
             error!("Failed to send payload through response channel: {error}");
         });
+        self.counters
+            .response_queue_depth
+            .set(self.response.max_capacity() - self.response.capacity());
     }
 
     /// Send an `ACK` frame.
@@ -1,22 +1,23 @@
 //! Receive-side frame buffer for `ASHv2` serial input.
 //!
-//! The buffer consumes chunks from the async serial stream, applies `ASHv2` control-byte
-//! handling byte by byte, un-stuffs completed frames, and converts the resulting bytes into
-//! typed [`Frame`] values.
+//! The buffer consumes chunks from the async serial stream, passes them byte by byte to a
+//! [`Deframer`], which applies `ASHv2` control-byte handling and un-stuffs completed frames, and
+//! converts the resulting bytes into typed [`Frame`] values.
 
-use std::io::{ErrorKind, Result};
-use std::vec::Drain;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Arc;
 
 use bytes::Bytes;
-use log::{debug, trace, warn};
+use log::trace;
 use tokio::io::AsyncRead;
 use tokio_stream::StreamExt;
 use tokio_util::io::ReaderStream;
 
+use crate::deframer::Deframer;
 use crate::frame::Frame;
 use crate::hex_slice::HexSlice;
-use crate::protocol::{ControlByte, Unstuff};
-use crate::types::MAX_FRAME_SIZE;
+use crate::observer::{Direction, Tap};
+use crate::stats::Counters;
 
 /// Receive-side buffer that reconstructs `ASHv2` frames from serial bytes.
 #[derive(Debug)]
@@ -25,8 +26,12 @@ pub struct Buffer<T> {
     reader: ReaderStream<T>,
     /// Iterator over the currently buffered chunk.
     chunk: <Bytes as IntoIterator>::IntoIter,
-    /// Accumulates the current raw frame until a `FLAG` byte terminates it.
-    frame: Vec<u8>,
+    /// Reconstructs frames from the received bytes.
+    deframer: Deframer,
+    /// Link statistics updated while reading frames.
+    counters: Arc<Counters>,
+    /// Observer notified of every completed frame.
+    tap: Tap,
 }
 
 impl<T> Buffer<T>
@@ -35,13 +40,24 @@ where
 {
     /// Create a new receive buffer around a serial port.
     #[must_use]
-    pub fn new(reader: T) -> Self {
+    pub fn new(reader: T, counters: Arc<Counters>, tap: Tap) -> Self {
         Self {
             reader: ReaderStream::new(reader),
             chunk: Bytes::new().into_iter(),
-            frame: Vec::with_capacity(MAX_FRAME_SIZE),
+            deframer: Deframer::new(),
+            counters,
+            tap,
         }
     }
+
+    /// Replace the serial reader, e.g. after the transport has been reopened.
+    ///
+    /// Buffered bytes of the previous reader are discarded.
+    pub fn replace(&mut self, reader: T) {
+        self.reader = ReaderStream::new(reader);
+        self.chunk = Bytes::new().into_iter();
+        self.deframer.reset();
+    }
 }
 
 impl<T> Buffer<T>
@@ -51,72 +67,41 @@ where
     /// Read the next complete `ASHv2` [`Frame`].
     ///
     /// The method waits until a complete frame is terminated by `FLAG`, then applies byte
-    /// unstuffing and frame parsing before returning.
+    /// unstuffing and frame parsing before returning. The observer is notified of the frame
+    /// or the reason why it could not be parsed.
     ///
     /// # Errors
     ///
     /// Returns an error if serial I/O fails, the byte stream ends before another frame is
-    /// available, or the completed frame cannot be parsed.
+    /// available, or the completed frame cannot be parsed. Errors of the latter kind have
+    /// [`ErrorKind::InvalidData`] and wrap the [`DecodeError`](crate::frame::DecodeError).
     pub async fn read_frame(&mut self) -> Result<Option<Frame>> {
-        self.read_raw_frame().await?.try_into().map(Some)
+        let frame = Frame::try_from(self.read_raw_frame().await?);
+        self.tap.notify(
+            Direction::Received,
+            frame.as_ref().map_err(|error| *error),
+            self.deframer.raw(),
+        );
+        frame.map(Some).map_err(|error| {
+            self.counters.decode_errors.increment();
+            Error::new(ErrorKind::InvalidData, error)
+        })
     }
 
-    async fn read_raw_frame(&mut self) -> Result<Drain<'_, u8>> {
-        self.reset_frame();
-        let mut error = false;
+    async fn read_raw_frame(&mut self) -> Result<&[u8]> {
+        self.deframer.reset();
 
         while let Some(byte) = self.next_byte().await? {
-            match ControlByte::try_from(byte) {
-                Ok(control_byte) => match control_byte {
-                    ControlByte::Cancel => {
-                        trace!("Resetting buffer due to cancel byte.");
-                        self.reset_frame();
-                        error = false;
-                    }
-                    ControlByte::Flag => {
-                        trace!("Received flag byte.");
-
-                        if !error && !self.frame.is_empty() {
-                            debug!("Received frame.");
-                            trace!("Buffer: {:#04X}", HexSlice::new(&self.frame));
-                            self.frame.unstuff();
-                            trace!("Unstuffed buffer: {:#04X}", HexSlice::new(&self.frame));
-                            self.warn_if_frame_exceeds_max_frame_size();
-                            return Ok(self.frame.drain(..));
-                        }
-
-                        trace!("Resetting buffer due to error or empty buffer.");
-                        trace!("Error condition was: {error}");
-                        trace!("Buffer: {:#04X}", HexSlice::new(&self.frame));
-                        self.reset_frame();
-                        error = false;
-                    }
-                    ControlByte::Substitute => {
-                        trace!("Received SUBSTITUTE byte. Setting error condition.");
-                        error = true;
-                    }
-                    ControlByte::Xon => {
-                        trace!("NCP requested to resume transmission.");
-                    }
-                    ControlByte::Xoff => {
-                        trace!("NCP requested to stop transmission.");
-                    }
-                    ControlByte::Wake => {
-                        if self.frame.is_empty() {
-                            debug!("NCP tried to wake us up.");
-                        } else {
-                            self.frame.push(control_byte.into());
-                        }
-                    }
-                },
-                Err(byte) => {
-                    self.frame.push(byte);
-                }
+            if self.deframer.push(byte, &self.counters) {
+                return Ok(self.deframer.frame());
             }
         }
 
-        trace!("Buffer state: {:#04X}", HexSlice::new(&self.frame));
-        self.warn_if_frame_exceeds_max_frame_size();
+        trace!(
+            "Buffer state: {:#04X}",
+            HexSlice::new(self.deframer.frame())
+        );
+        self.deframer.warn_if_frame_exceeds_max_frame_size();
         Err(ErrorKind::UnexpectedEof.into())
     }
 
@@ -128,6 +113,7 @@ where
 
             match self.reader.next().await {
                 Some(Ok(bytes)) => {
+                    self.counters.bytes_received.add(bytes.len());
                     self.chunk = bytes.into_iter();
                 }
                 Some(Err(error)) => return Err(error),
@@ -135,17 +121,6 @@ where
             }
         }
     }
-
-    fn reset_frame(&mut self) {
-        self.frame.clear();
-        self.frame.shrink_to(MAX_FRAME_SIZE);
-    }
-
-    fn warn_if_frame_exceeds_max_frame_size(&self) {
-        if self.frame.len() > MAX_FRAME_SIZE {
-            warn!("Receiver frame buffer exceeded maximum frame size of {MAX_FRAME_SIZE} bytes.");
-        }
-    }
 }
 
 #[cfg(test)]
@@ -155,10 +130,10 @@ mod tests {
     use tokio::runtime::Builder;
 
     use super::*;
+    use crate::protocol::ControlByte;
 
     const FIRST_FRAME_BYTE: u8 = 0x01;
     const SECOND_FRAME_BYTE: u8 = 0x02;
-    const EXTRA_FRAME_BYTES: usize = 1;
 
     #[test]
     fn read_raw_frame_keeps_remaining_chunk_bytes() {
@@ -168,18 +143,18 @@ mod tests {
             .block_on(async {
                 let flag = u8::from(ControlByte::Flag);
                 let input = [FIRST_FRAME_BYTE, flag, SECOND_FRAME_BYTE, flag];
-                let mut buffer = Buffer::new(Cursor::new(input));
+                let mut buffer = Buffer::new(Cursor::new(input), Arc::default(), Tap::default());
 
-                let first_frame: Vec<_> = buffer
+                let first_frame = buffer
                     .read_raw_frame()
                     .await
                     .expect("first frame should be readable")
-                    .collect();
-                let second_frame: Vec<_> = buffer
+                    .to_vec();
+                let second_frame = buffer
                     .read_raw_frame()
                     .await
                     .expect("second frame should be readable")
-                    .collect();
+                    .to_vec();
 
                 assert_eq!(first_frame, [FIRST_FRAME_BYTE]);
                 assert_eq!(second_frame, [SECOND_FRAME_BYTE]);
@@ -187,32 +162,38 @@ mod tests {
     }
 
     #[test]
-    fn read_raw_frame_shrinks_before_reading_again() {
+    fn read_raw_frame_counts_control_bytes() {
         Builder::new_current_thread()
             .build()
             .expect("runtime should build")
             .block_on(async {
                 let flag = u8::from(ControlByte::Flag);
-                let mut input = vec![FIRST_FRAME_BYTE; MAX_FRAME_SIZE + EXTRA_FRAME_BYTES];
-                input.push(flag);
-                input.push(SECOND_FRAME_BYTE);
-                input.push(flag);
-                let mut buffer = Buffer::new(Cursor::new(input));
-
-                let oversized_frame: Vec<_> = buffer
-                    .read_raw_frame()
-                    .await
-                    .expect("oversized frame should be readable")
-                    .collect();
-                let second_frame: Vec<_> = buffer
+                let input = [
+                    FIRST_FRAME_BYTE,
+                    ControlByte::Substitute.into(),
+                    flag,
+                    FIRST_FRAME_BYTE,
+                    ControlByte::Cancel.into(),
+                    SECOND_FRAME_BYTE,
+                    0x7D,
+                    0x31,
+                    flag,
+                ];
+                let counters = Arc::<Counters>::default();
+                let mut buffer = Buffer::new(Cursor::new(input), counters.clone(), Tap::default());
+
+                let frame = buffer
                     .read_raw_frame()
                     .await
-                    .expect("second frame should be readable")
-                    .collect();
-
-                assert_eq!(oversized_frame.len(), MAX_FRAME_SIZE + EXTRA_FRAME_BYTES);
-                assert_eq!(second_frame, [SECOND_FRAME_BYTE]);
-                assert!(buffer.frame.capacity() <= MAX_FRAME_SIZE);
+                    .expect("frame should be readable")
+                    .to_vec();
+
+                assert_eq!(frame, [SECOND_FRAME_BYTE, 0x11]);
+                let stats = counters.snapshot();
+                assert_eq!(stats.substitutes, 1);
+                assert_eq!(stats.cancels, 1);
+                assert_eq!(stats.bytes_received, 9);
+                assert_eq!(stats.stuffing_overhead_received, 1);
             });
     }
 }
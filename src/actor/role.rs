@@ -0,0 +1,13 @@
+//! Roles of the local end of an `ASHv2` link.
+
+use crate::code::Code;
+
+/// Role that the actor plays on an `ASHv2` link.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Role {
+    /// Host, which establishes the connection by sending `RST` frames.
+    Host,
+
+    /// NCP, which answers `RST` frames with `RST_ACK` frames carrying the given reset code.
+    Ncp(Code),
+}
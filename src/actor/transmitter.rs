@@ -1,5 +1,3 @@
-use std::io;
-use std::io::ErrorKind;
 use std::ops::BitAnd;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
@@ -9,11 +7,18 @@ use std::time::{Duration, Instant};
 use log::{debug, error, info, trace, warn};
 use tokio::io::AsyncWrite;
 use tokio::sync::mpsc::{Receiver, WeakSender};
+use tokio::time::sleep;
 
 use self::buffer::Buffer;
 use self::transmission::Transmission;
 use crate::actor::message::Message;
-use crate::frame::{Ack, Data, Error, Nak, RST, Rst, RstAck};
+use crate::actor::role::Role;
+use crate::actor::transport::Reopen;
+use crate::code::Code;
+use crate::error::Error;
+use crate::frame::{self, Ack, Data, Nak, RST, Rst, RstAck};
+use crate::observer::Tap;
+use crate::stats::Counters;
 use crate::status::Status;
 use crate::types::{MAX_FRAME_SIZE, Payload};
 use crate::{SEQ_MASK, T_RSTACK_MAX_MILLIS, T_RX_ACK_MAX_MILLIS, TX_K};
@@ -26,11 +31,13 @@ const T_RSTACK_MAX: Duration = Duration::from_millis(T_RSTACK_MAX_MILLIS);
 
 const T_RX_ACK_MAX: Duration = Duration::from_millis(T_RX_ACK_MAX_MILLIS);
 
-const TRANSMITTER_CHANNEL_CLOSED: &str = "ASHv2 transmitter channel is closed";
+/// Delay between attempts to reopen the transport.
+const REOPEN_DELAY: Duration = Duration::from_secs(1);
 
 /// `ASHv2` transmitter.
 #[derive(Debug)]
 pub struct Transmitter<T> {
+    role: Role,
     buffer: Buffer<T>,
     messages: Receiver<Message>,
     requeue: WeakSender<Message>,
@@ -39,14 +46,25 @@ pub struct Transmitter<T> {
     transmissions: heapless::Vec<Transmission, TX_K>,
     frame_number: u8,
     ack_number: u8,
+    counters: Arc<Counters>,
+    reopener: Option<Box<dyn Reopen<T>>>,
+    generation: u64,
 }
 
 impl<T> Transmitter<T> {
     /// Creates a new `ASHv2` transmitter.
     #[must_use]
-    pub const fn new(writer: T, messages: Receiver<Message>, requeue: WeakSender<Message>) -> Self {
+    pub fn new(
+        writer: T,
+        messages: Receiver<Message>,
+        requeue: WeakSender<Message>,
+        role: Role,
+        counters: Arc<Counters>,
+        tap: Tap,
+    ) -> Self {
         Self {
-            buffer: Buffer::new(writer),
+            role,
+            buffer: Buffer::new(writer, counters.clone(), tap),
             messages,
             requeue,
             status: Status::Uninitialized,
@@ -54,8 +72,18 @@ impl<T> Transmitter<T> {
             transmissions: heapless::Vec::new(),
             frame_number: 0,
             ack_number: 0,
+            counters,
+            reopener: None,
+            generation: 0,
         }
     }
+
+    /// Reopen the transport with `reopener` after fatal I/O errors.
+    #[must_use]
+    pub fn with_reopener(mut self, reopener: Box<dyn Reopen<T>>) -> Self {
+        self.reopener.replace(reopener);
+        self
+    }
 }
 
 impl<T> Transmitter<T>
@@ -65,16 +93,30 @@ where
     /// Runs the transmitter, processing messages from the channel.
     pub async fn run(mut self, running: Arc<AtomicBool>) {
         trace!("Starting transmitter with frame size: {MAX_FRAME_SIZE}");
-        self.reset().await.unwrap_or_else(|error| {
-            error!("Failed to send initial RST frame: {error}");
-        });
+
+        if self.role == Role::Host {
+            if let Err(error) = self.reset().await {
+                error!("Failed to send initial RST frame: {error}");
+
+                if matches!(error, Error::Io(_)) {
+                    self.reopen().await;
+                }
+            }
+        } else {
+            debug!("Waiting for RST frame from host.");
+        }
 
         while let Some(message) = self.messages.recv().await {
             trace!("Received message: {message}");
+            self.counters.message_queue_depth.set(self.messages.len());
 
             if let Err(error) = self.handle_message(message).await {
                 error!("Resetting connection due to I/O error: {error}");
                 self.status = Status::Failed;
+
+                if matches!(error, Error::Io(_)) {
+                    self.reopen().await;
+                }
             }
         }
 
@@ -82,30 +124,24 @@ where
         info!("Transmitter loop terminated.");
     }
 
-    async fn handle_message(&mut self, message: Message) -> io::Result<()> {
-        if self.status != Status::Connected {
-            if let Message::RstAck(ack) = message {
-                return self.handle_rst_ack(ack).await;
-            }
-
-            trace!("Received message before connection was established. Re-queueing.");
-            self.requeue(message).await?;
-
-            // Only log if the connection has failed, not if it hasn't been established yet.
-            if self.status == Status::Failed {
-                warn!("ASHv2 Connection failed. Resetting...");
-            }
-
-            return self.reset().await;
-        }
-
+    async fn handle_message(&mut self, message: Message) -> Result<(), Error> {
         match message {
+            // Reopen requests must be served in every state of the connection.
+            Message::Reopen(generation) => {
+                self.handle_reopen(generation).await;
+                Ok(())
+            }
+            message if self.status != Status::Connected => match self.role {
+                Role::Host => self.handle_message_before_rst_ack(message).await,
+                Role::Ncp(_) => self.handle_message_before_rst(message).await,
+            },
             Message::Payload {
                 payload,
                 response_tx: response,
             } => self.handle_payload(payload, response).await,
             Message::Ack(ack_num) => self.send_ack(ack_num).await,
             Message::Nak(ack_num) => self.send_nak(ack_num).await,
+            Message::SendError(code) => self.send_error(code).await,
             Message::Rst(rst) => self.handle_rst(rst).await,
             Message::RstAck(rst_ack) => self.handle_rst_ack(rst_ack).await,
             Message::Error(error) => self.handle_error(error).await,
@@ -117,11 +153,44 @@ where
         }
     }
 
+    /// Handle messages as a host whose connection has not been acknowledged by the NCP.
+    async fn handle_message_before_rst_ack(&mut self, message: Message) -> Result<(), Error> {
+        if let Message::RstAck(ack) = message {
+            return self.handle_rst_ack(ack).await;
+        }
+
+        // Only log if the connection has failed, not if it hasn't been established yet.
+        if self.status == Status::Failed {
+            warn!("ASHv2 Connection failed. Resetting...");
+        }
+
+        trace!("Received message before connection was established. Re-queueing.");
+        self.requeue(message).await?;
+
+        self.reset().await
+    }
+
+    /// Handle messages as an NCP that has not been reset by the host.
+    async fn handle_message_before_rst(&mut self, message: Message) -> Result<(), Error> {
+        match message {
+            Message::Rst(rst) => self.handle_rst(rst).await,
+            Message::SendError(code) => self.send_error(code).await,
+            message @ Message::Payload { .. } => {
+                trace!("Received payload before connection was established. Re-queueing.");
+                self.requeue(message).await
+            }
+            message => {
+                trace!("Discarding message before connection was established: {message}");
+                Ok(())
+            }
+        }
+    }
+
     async fn handle_payload(
         &mut self,
         payload: Box<Payload>,
-        response: tokio::sync::oneshot::Sender<io::Result<()>>,
-    ) -> io::Result<()> {
+        response: tokio::sync::oneshot::Sender<Result<u64, Error>>,
+    ) -> Result<(), Error> {
         if self.transmissions.is_full() {
             warn!("Insufficient space in transmission queue for payload, requeueing.");
             return self
@@ -133,37 +202,67 @@ where
         }
 
         let data = Data::new(self.next_frame_number(), self.ack_number, *payload);
-        // With a sliding windows size > 1 the NCP may enter an "ERROR: Assert" state when sending
-        // fragmented messages if each DATA frame's ACK number is not increased.
-        self.ack_number = self.ack_number.wrapping_add(1).bitand(SEQ_MASK);
+
+        if self.role == Role::Host {
+            // With a sliding windows size > 1 the NCP may enter an "ERROR: Assert" state when
+            // sending fragmented messages if each DATA frame's ACK number is not increased.
+            self.ack_number = self.ack_number.wrapping_add(1).bitand(SEQ_MASK);
+        }
+
+        let result = self.transmit(data.into()).await;
         response
-            .send(self.transmit(data.into()).await)
+            .send(result.map(|()| self.counters.connection()))
             .unwrap_or_else(|_| {
                 error!("Failed to send transmit result through response channel.");
             });
         Ok(())
     }
 
-    async fn send_ack(&mut self, ack_num: u8) -> io::Result<()> {
+    async fn send_ack(&mut self, ack_num: u8) -> Result<(), Error> {
         self.ack_number = ack_num;
-        self.buffer.write_frame(Ack::new(ack_num, false)).await
+        self.buffer.write_frame(Ack::new(ack_num, false)).await?;
+        self.counters.acks_sent.increment();
+        Ok(())
     }
 
-    async fn send_nak(&mut self, ack_num: u8) -> io::Result<()> {
-        self.buffer.write_frame(Nak::new(ack_num, false)).await
+    async fn send_nak(&mut self, ack_num: u8) -> Result<(), Error> {
+        self.buffer.write_frame(Nak::new(ack_num, false)).await?;
+        self.counters.naks_sent.increment();
+        Ok(())
     }
 
-    /// Handle RST frame received from the NCP.
-    async fn handle_rst(&mut self, rst: Rst) -> io::Result<()> {
-        error!("Received RST frame: {rst}, resetting connection.");
+    /// Send an `ERROR` frame and enter the failed state.
+    async fn send_error(&mut self, code: Code) -> Result<(), Error> {
+        warn!("Sending ERROR frame: {code}");
         self.status = Status::Failed;
-        self.reset().await
+        self.buffer.write_frame(frame::Error::new(code)).await
+    }
+
+    /// Handle RST frame received from the peer.
+    async fn handle_rst(&mut self, rst: Rst) -> Result<(), Error> {
+        let Role::Ncp(code) = self.role else {
+            error!("Received RST frame: {rst}, resetting connection.");
+            self.status = Status::Failed;
+            return self.reset().await;
+        };
+
+        debug!("Received RST frame: {rst}, acknowledging reset.");
+        self.reset_sequence();
+        self.buffer.write_frame(RstAck::new(code)).await?;
+        self.status = Status::Connected;
+        self.counters.count_connection();
+        Ok(())
     }
 
     /// Handle RST ACK frame received from the NCP.
-    async fn handle_rst_ack(&mut self, rst_ack: RstAck) -> io::Result<()> {
+    async fn handle_rst_ack(&mut self, rst_ack: RstAck) -> Result<(), Error> {
         trace!("Received RST ACK frame: {rst_ack}, connection reset acknowledged.");
 
+        if self.role != Role::Host {
+            warn!("Received unexpected RST ACK frame as NCP: {rst_ack}.");
+            return Ok(());
+        }
+
         if !rst_ack.is_ash_v2() {
             error!("Received RST ACK frame with invalid ASH version: {rst_ack}.");
             return Ok(());
@@ -172,7 +271,9 @@ where
         if let Some(timestamp) = self.last_rst_sent.take() {
             if timestamp.elapsed() < T_RSTACK_MAX {
                 debug!("Connection established successfully.");
+                self.reset_sequence();
                 self.status = Status::Connected;
+                self.counters.count_connection();
                 Ok(())
             } else {
                 warn!("RST ACK received after timeout. Resetting connection again.");
@@ -185,7 +286,12 @@ where
     }
 
     /// Handle errors received from the NCP.
-    async fn handle_error(&mut self, error: Error) -> io::Result<()> {
+    async fn handle_error(&mut self, error: frame::Error) -> Result<(), Error> {
+        if self.role != Role::Host {
+            warn!("Received unexpected ERROR frame as NCP: {error}.");
+            return Ok(());
+        }
+
         warn!("Transmitter encountered error: {error}, resetting connection.");
         self.status = Status::Failed;
         self.reset().await
@@ -211,11 +317,14 @@ where
                 "ACKed frame {transmission} after {:?}",
                 transmission.elapsed()
             );
+            self.counters.ack_latency.record(transmission.elapsed());
         }
+
+        self.update_window_occupancy();
     }
 
     /// Retransmit `DATA` frames that have been `NAK`ed by the NCP.
-    async fn nak_sent_frames(&mut self, nak_num: u8) -> io::Result<()> {
+    async fn nak_sent_frames(&mut self, nak_num: u8) -> Result<(), Error> {
         // Remove timed-out transmissions.
         self.transmissions
             .retain(|transmission| !transmission.is_timed_out(T_RX_ACK_MAX));
@@ -231,21 +340,32 @@ where
             self.transmit(transmission).await?;
         }
 
+        self.update_window_occupancy();
         Ok(())
     }
 
     /// Send a `DATA` frame.
-    async fn transmit(&mut self, mut transmission: Transmission) -> io::Result<()> {
+    async fn transmit(&mut self, mut transmission: Transmission) -> Result<(), Error> {
         let data = transmission.data_for_transmit()?;
         trace!("Transmitting frame {data:#04X}");
+        let is_retransmission = data.is_retransmission();
         self.buffer.write_frame(data).await?;
+
+        if is_retransmission {
+            self.counters.retransmissions.increment();
+        } else {
+            self.counters.data_frames_sent.increment();
+        }
+
         self.transmissions
             .insert(0, transmission)
-            .map_err(|_| io::Error::new(ErrorKind::OutOfMemory, "Failed to enqueue retransmit"))
+            .map_err(|_| Error::TransmissionQueueFull)?;
+        self.update_window_occupancy();
+        Ok(())
     }
 
     /// Send RST frame to reset the connection.
-    async fn reset(&mut self) -> io::Result<()> {
+    async fn reset(&mut self) -> Result<(), Error> {
         if let Some(timestamp) = self.last_rst_sent.take()
             && timestamp.elapsed() < T_RSTACK_MAX
         {
@@ -255,7 +375,73 @@ where
         }
 
         self.last_rst_sent.replace(Instant::now());
-        self.buffer.write_frame(RST).await
+        self.buffer.write_frame(RST).await?;
+        self.counters.rsts_sent.increment();
+        Ok(())
+    }
+
+    /// Handle the receiver's request to reopen its failed transport.
+    async fn handle_reopen(&mut self, generation: u64) {
+        if generation == self.generation {
+            self.reopen().await;
+        } else {
+            trace!("Transport of generation {generation} has already been reopened.");
+        }
+    }
+
+    /// Reopen the transport and reset the connection, retrying until it succeeds.
+    ///
+    /// Does nothing if the actor has been started without a transport factory.
+    async fn reopen(&mut self) {
+        loop {
+            let Some(reopener) = self.reopener.as_mut() else {
+                return;
+            };
+
+            if reopener.is_closed() {
+                debug!("Receiver terminated, not reopening transport.");
+                return;
+            }
+
+            let writer = match reopener.reopen().await {
+                Ok(writer) => writer,
+                Err(error) => {
+                    warn!("Failed to reopen transport: {error}");
+                    sleep(REOPEN_DELAY).await;
+                    continue;
+                }
+            };
+
+            info!("Transport reopened.");
+            self.buffer.replace(writer);
+            self.generation = self.generation.wrapping_add(1);
+            self.last_rst_sent = None;
+
+            let Role::Host = self.role else {
+                self.status = Status::Uninitialized;
+                return;
+            };
+
+            self.status = Status::Failed;
+
+            match self.reset().await {
+                Ok(()) => return,
+                Err(error) => error!("Failed to send RST frame on reopened transport: {error}"),
+            }
+        }
+    }
+
+    /// Reset frame and ACK numbers and discard pending transmissions after a link reset.
+    fn reset_sequence(&mut self) {
+        self.frame_number = 0;
+        self.ack_number = 0;
+        self.transmissions.clear();
+        self.update_window_occupancy();
+    }
+
+    /// Publish the number of transmissions awaiting acknowledgement.
+    fn update_window_occupancy(&self) {
+        self.counters.window_occupancy.set(self.transmissions.len());
     }
 
     /// Returns the next frame number.
@@ -265,41 +451,24 @@ where
         frame_number
     }
 
-    fn reject_message(message: Message, kind: ErrorKind, reason: &'static str) -> io::Result<()> {
+    /// Answer a payload message with the given error.
+    fn reject_message(message: Message, error: Error) {
         if let Message::Payload { response_tx, .. } = message {
-            response_tx
-                .send(Err(io::Error::new(kind, reason)))
-                .unwrap_or_else(|_| {
-                    error!("Failed to send transmit result through response channel.");
-                });
+            response_tx.send(Err(error)).unwrap_or_else(|_| {
+                error!("Failed to send transmit result through response channel.");
+            });
         }
-
-        Err(io::Error::new(kind, reason))
     }
 
-    async fn requeue(&self, message: Message) -> io::Result<()> {
+    async fn requeue(&self, message: Message) -> Result<(), Error> {
         let Some(sender) = self.requeue.upgrade() else {
-            return Self::reject_message(
-                message,
-                ErrorKind::BrokenPipe,
-                TRANSMITTER_CHANNEL_CLOSED,
-            );
+            Self::reject_message(message, Error::ActorShutDown);
+            return Err(Error::ActorShutDown);
         };
 
         sender.send(message).await.map_err(|error| {
-            let message = error.0;
-            if let Message::Payload { response_tx, .. } = message {
-                response_tx
-                    .send(Err(io::Error::new(
-                        ErrorKind::BrokenPipe,
-                        TRANSMITTER_CHANNEL_CLOSED,
-                    )))
-                    .unwrap_or_else(|_| {
-                        error!("Failed to send transmit result through response channel.");
-                    });
-            }
-
-            io::Error::new(ErrorKind::BrokenPipe, TRANSMITTER_CHANNEL_CLOSED)
+            Self::reject_message(error.0, Error::ActorShutDown);
+            Error::ActorShutDown
         })
     }
 }
@@ -1,13 +1,19 @@
 //! Transmit-side frame buffer for `ASHv2` serial output.
 
 use core::fmt::{Display, UpperHex};
-use std::io::{self, Error};
+use std::sync::Arc;
 
 use log::{debug, trace};
 use tokio::io::{AsyncWrite, AsyncWriteExt};
 
+#[cfg(feature = "tracing")]
+use crate::actor::fields::FrameFields;
+use crate::error::Error;
+use crate::frame::Frame;
 use crate::hex_slice::HexSlice;
+use crate::observer::{Direction, Tap};
 use crate::protocol::{ControlByte, Stuff};
+use crate::stats::Counters;
 use crate::types::RawFrame;
 
 /// Transmit-side buffer that encodes `ASHv2` frames for serial writes.
@@ -17,17 +23,28 @@ pub struct Buffer<T> {
     inner: T,
     /// Reusable frame buffer used for stuffing and termination.
     frame: RawFrame,
+    /// Link statistics updated while writing frames.
+    counters: Arc<Counters>,
+    /// Observer notified of every written frame.
+    tap: Tap,
 }
 
 impl<T> Buffer<T> {
     /// Create a new transmit buffer around an async writer.
     #[must_use]
-    pub const fn new(inner: T) -> Self {
+    pub const fn new(inner: T, counters: Arc<Counters>, tap: Tap) -> Self {
         Self {
             inner,
             frame: RawFrame::new(),
+            counters,
+            tap,
         }
     }
+
+    /// Replace the async writer, e.g. after the transport has been reopened.
+    pub fn replace(&mut self, inner: T) {
+        self.inner = inner;
+    }
 }
 
 impl<T> Buffer<T>
@@ -39,7 +56,7 @@ where
     /// # Errors
     ///
     /// Returns an error if the write operation failed or the frame buffer overflowed.
-    pub async fn write_frame<F>(&mut self, frame: F) -> io::Result<()>
+    pub async fn write_frame<F>(&mut self, frame: F) -> Result<(), Error>
     where
         F: IntoIterator<Item = u8> + Display + UpperHex,
     {
@@ -48,13 +65,35 @@ where
         self.frame.clear();
         self.frame.extend(frame);
         trace!("Frame bytes: {:#04X}", HexSlice::new(&self.frame));
-        self.frame.stuff()?;
+        #[cfg(feature = "tracing")]
+        let traced = FrameFields::enabled();
+        #[cfg(not(feature = "tracing"))]
+        let traced = false;
+        let decoded =
+            (traced || self.tap.is_attached()).then(|| Frame::try_from(self.frame.as_slice()));
+        let unstuffed_size = self.frame.len();
+        self.frame.stuff().map_err(Error::BufferOverflow)?;
+        let stuffing_overhead = self.frame.len() - unstuffed_size;
         trace!("Stuffed bytes: {:#04X}", HexSlice::new(&self.frame));
         self.frame
             .push(ControlByte::Flag.into())
-            .map_err(|byte| Error::other(format!("Frame buffer overflow: {byte:#04X}")))?;
+            .map_err(Error::BufferOverflow)?;
         trace!("Writing bytes: {:#04X}", HexSlice::new(&self.frame));
         self.inner.write_all(&self.frame).await?;
-        self.inner.flush().await
+        self.counters.bytes_sent.add(self.frame.len());
+        self.counters.stuffing_overhead_sent.add(stuffing_overhead);
+        if let Some(decoded) = &decoded {
+            self.tap.notify(
+                Direction::Sent,
+                decoded.as_ref().map_err(|error| *error),
+                &self.frame,
+            );
+            #[cfg(feature = "tracing")]
+            if traced && let Ok(frame) = decoded {
+                FrameFields::from(frame).trace(Direction::Sent);
+            }
+        }
+
+        self.inner.flush().await.map_err(Error::Io)
     }
 }
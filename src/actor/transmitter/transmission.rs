@@ -2,9 +2,9 @@
 
 use core::fmt::Display;
 use core::time::Duration;
-use std::io::{self, Error, ErrorKind};
 use std::time::Instant;
 
+use crate::error::Error;
 use crate::frame::Data;
 
 const ACK_TIMEOUTS: usize = 4;
@@ -41,7 +41,7 @@ impl Transmission {
     /// # Errors
     ///
     /// Returns an [`Error`] if the retransmission limit is exceeded.
-    pub fn data_for_transmit(&mut self) -> io::Result<&Data> {
+    pub fn data_for_transmit(&mut self) -> Result<&Data, Error> {
         self.transmits += 1;
 
         if self.transmits > 1 {
@@ -49,13 +49,9 @@ impl Transmission {
         }
 
         if self.transmits >= ACK_TIMEOUTS {
-            return Err(Error::new(
-                ErrorKind::TimedOut,
-                format!(
-                    "Retransmission limit of frame #{} exceeded.",
-                    self.data.frame_num()
-                ),
-            ));
+            return Err(Error::RetransmissionLimitExceeded {
+                frame_num: self.data.frame_num(),
+            });
         }
 
         Ok(&self.data)
@@ -0,0 +1,193 @@
+//! Reopening of the transport after fatal I/O errors.
+
+use std::fmt::{Debug, Formatter};
+use std::io::{self, ErrorKind};
+use std::pin::Pin;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+/// Factory that opens the transport of the `ASHv2` actor.
+///
+/// The actor calls [`TransportFactory::open`] again whenever reading from or writing to the
+/// current transport fails fatally, e.g. because a USB NCP re-enumerated, and then re-runs the
+/// reset handshake on the new transport.
+///
+/// The trait is implemented for closures returning a future of the reader and writer:
+///
+/// ```no_run
+/// # async fn open_device() -> std::io::Result<tokio::io::DuplexStream> {
+/// #     Ok(tokio::io::duplex(64).0)
+/// # }
+/// # async fn run(response_tx: tokio::sync::mpsc::Sender<ashv2::Payload>) -> std::io::Result<()> {
+/// let (handle, futures) = ashv2::start_with_factory(
+///     || async { open_device().await.map(tokio::io::split) },
+///     response_tx,
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait TransportFactory: Send + Sync + 'static {
+    /// The reading half of the transport.
+    type Reader: AsyncRead + Send + Sync + Unpin + 'static;
+
+    /// The writing half of the transport.
+    type Writer: AsyncWrite + Send + Sync + Unpin + 'static;
+
+    /// Open the transport.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transport could not be opened. The actor retries periodically.
+    fn open(&mut self) -> impl Future<Output = io::Result<(Self::Reader, Self::Writer)>> + Send;
+}
+
+impl<F, O, R, W> TransportFactory for F
+where
+    F: FnMut() -> O + Send + Sync + 'static,
+    O: Future<Output = io::Result<(R, W)>> + Send,
+    R: AsyncRead + Send + Sync + Unpin + 'static,
+    W: AsyncWrite + Send + Sync + Unpin + 'static,
+{
+    type Reader = R;
+    type Writer = W;
+
+    fn open(&mut self) -> impl Future<Output = io::Result<(R, W)>> + Send {
+        self()
+    }
+}
+
+/// Opens a new transport on behalf of the transmitter.
+pub trait Reopen<W>: Debug + Send + Sync {
+    /// Open a new transport, hand its reader to the receiver and return its writer.
+    fn reopen(&mut self) -> Pin<Box<dyn Future<Output = io::Result<W>> + Send + '_>>;
+
+    /// Returns `true` if the receiver no longer accepts new readers.
+    fn is_closed(&self) -> bool;
+}
+
+/// Channels that let the actor reopen its transport.
+#[derive(Debug)]
+pub struct Reopening<R, W> {
+    /// New readers for the receiver.
+    pub readers: UnboundedReceiver<R>,
+    /// Opens new transports on behalf of the transmitter.
+    pub reopener: Box<dyn Reopen<W>>,
+}
+
+/// Reopens the transport with a [`TransportFactory`] and passes new readers to the receiver.
+pub struct Reopener<F>
+where
+    F: TransportFactory,
+{
+    factory: F,
+    readers: UnboundedSender<F::Reader>,
+}
+
+impl<F> Reopener<F>
+where
+    F: TransportFactory,
+{
+    /// Create the channels to reopen the transport with `factory`.
+    pub fn reopening(factory: F) -> Reopening<F::Reader, F::Writer> {
+        let (readers, receiver) = tokio::sync::mpsc::unbounded_channel();
+        Reopening {
+            readers: receiver,
+            reopener: Box::new(Self { factory, readers }),
+        }
+    }
+}
+
+impl<F> Debug for Reopener<F>
+where
+    F: TransportFactory,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reopener").finish_non_exhaustive()
+    }
+}
+
+impl<F> Reopen<F::Writer> for Reopener<F>
+where
+    F: TransportFactory,
+{
+    fn reopen(&mut self) -> Pin<Box<dyn Future<Output = io::Result<F::Writer>> + Send + '_>> {
+        Box::pin(async move {
+            let (reader, writer) = self.factory.open().await?;
+            self.readers
+                .send(reader)
+                .map_err(|_| io::Error::new(ErrorKind::BrokenPipe, "receiver terminated"))?;
+            Ok(writer)
+        })
+    }
+
+    fn is_closed(&self) -> bool {
+        self.readers.is_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, split};
+    use tokio::runtime::Builder;
+    use tokio::sync::mpsc::{channel, unbounded_channel};
+
+    use crate::code::Code;
+    use crate::types::Payload;
+
+    const BUFFER_SIZE: usize = 1024;
+    const CHANNEL_SIZE: usize = 8;
+
+    #[test]
+    fn actor_reopens_transport_after_eof() {
+        Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (ncp_streams, mut opened) = unbounded_channel();
+                let factory = move || {
+                    let (host_stream, ncp_stream) = duplex(BUFFER_SIZE);
+                    let result = ncp_streams
+                        .send(ncp_stream)
+                        .map(|()| split(host_stream))
+                        .map_err(|_| std::io::ErrorKind::NotConnected.into());
+                    async move { result }
+                };
+                let (host_tx, mut host_rx) = channel(CHANNEL_SIZE);
+                let (host, host_futures) = crate::start_with_factory(factory, host_tx)
+                    .await
+                    .expect("transport should open");
+                tokio::spawn(host_futures.transmitter);
+                tokio::spawn(host_futures.receiver);
+
+                for round in 0..2_u8 {
+                    let ncp_stream = opened.recv().await.expect("factory should be called");
+                    let (ncp_reader, ncp_writer) = split(ncp_stream);
+                    let (ncp_tx, mut ncp_rx) = channel(CHANNEL_SIZE);
+                    let (ncp, ncp_futures) =
+                        crate::ncp::start(ncp_reader, ncp_writer, ncp_tx, Code::PowerOn);
+                    let transmitter = tokio::spawn(ncp_futures.transmitter);
+                    let receiver = tokio::spawn(ncp_futures.receiver);
+
+                    let request: Payload = [round, 0x00, 0x00, 0x02].into_iter().collect();
+                    host.send(request.clone()).await.expect("host should send");
+                    assert_eq!(ncp_rx.recv().await, Some(request));
+
+                    let response: Payload = [round, 0x80, 0x00, 0x02].into_iter().collect();
+                    ncp.send(response.clone()).await.expect("NCP should send");
+                    assert_eq!(host_rx.recv().await, Some(response));
+
+                    // Unplug the NCP by dropping its end of the transport.
+                    transmitter.abort();
+                    receiver.abort();
+                }
+
+                assert!(
+                    opened.recv().await.is_some(),
+                    "factory should be called after the second transport died"
+                );
+            });
+    }
+}
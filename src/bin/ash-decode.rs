@@ -0,0 +1,198 @@
+//! Decode `ASHv2` frames from raw or hex-encoded serial bytes.
+//!
+//! The input is read from a file or from stdin and printed as a timeline of frames with CRC
+//! failures, sequence anomalies and unmasked `DATA` payloads. With the `ezsp` feature, the EZSP
+//! headers of `DATA` payloads can be decoded as well.
+#![expect(unused_crate_dependencies)]
+
+use std::fs::read;
+use std::io::{Read, stdin};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use ashv2::replay::{Entry, Replay, parse_hex};
+use clap::{Parser, ValueEnum};
+
+/// Decode `ASHv2` frames from raw or hex-encoded serial bytes.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// The file to read the serial bytes from. Reads from stdin if omitted or `-`.
+    file: Option<PathBuf>,
+
+    /// The encoding of the input.
+    #[arg(short, long, value_enum, default_value_t = Format::Auto)]
+    format: Format,
+
+    /// Decode the EZSP headers of `DATA` payloads, assuming the given negotiated EZSP version.
+    #[cfg(feature = "ezsp")]
+    #[arg(short, long, value_name = "VERSION")]
+    ezsp: Option<u8>,
+}
+
+/// Encodings of the input.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum Format {
+    /// Hex if the input can be parsed as such, raw bytes otherwise.
+    Auto,
+    /// Whitespace or comma separated hex bytes.
+    Hex,
+    /// Raw serial bytes.
+    Raw,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let input = match read_input(args.file.as_ref()) {
+        Ok(input) => input,
+        Err(error) => {
+            eprintln!("Failed to read input: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let bytes = match decode_input(input, args.format) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let (entries, trailing) = decode_frames(&bytes);
+
+    for entry in entries {
+        println!("{entry}");
+        #[cfg(feature = "ezsp")]
+        if let Some(version) = args.ezsp {
+            print_ezsp_header(&entry, version);
+        }
+    }
+
+    if !trailing.is_empty() {
+        println!("Trailing bytes without FLAG: {trailing:02X?}");
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn read_input(file: Option<&PathBuf>) -> std::io::Result<Vec<u8>> {
+    match file {
+        Some(path) if path.as_os_str() != "-" => read(path),
+        _ => {
+            let mut input = Vec::new();
+            stdin().read_to_end(&mut input)?;
+            Ok(input)
+        }
+    }
+}
+
+fn decode_input(input: Vec<u8>, format: Format) -> Result<Vec<u8>, String> {
+    match format {
+        Format::Raw => Ok(input),
+        Format::Hex => str::from_utf8(&input)
+            .map_err(|error| format!("Input is not valid UTF-8: {error}"))
+            .and_then(|text| parse_hex(text).map_err(|error| error.to_string())),
+        Format::Auto => Ok(str::from_utf8(&input)
+            .ok()
+            .and_then(|text| parse_hex(text).ok())
+            .unwrap_or(input)),
+    }
+}
+
+/// Decode the frames of the serial bytes and return them with the bytes after the last `FLAG`.
+fn decode_frames(bytes: &[u8]) -> (Vec<Entry>, Vec<u8>) {
+    let mut replay = Replay::new();
+    let entries = replay.feed(bytes);
+    (entries, replay.pending().to_vec())
+}
+
+#[cfg(feature = "ezsp")]
+fn print_ezsp_header(entry: &Entry, version: u8) {
+    use ezsp::{Extended, Header, Legacy, MIN_NON_LEGACY_VERSION};
+    use le_stream::FromLeStream;
+
+    let Some(payload) = &entry.payload else {
+        return;
+    };
+
+    let stream = payload.iter().copied();
+    let header = if version >= MIN_NON_LEGACY_VERSION.get() {
+        Extended::from_le_stream(stream).map(Header::Extended)
+    } else {
+        Legacy::from_le_stream(stream).map(Header::Legacy)
+    };
+
+    match header {
+        Some(header) => println!("          EZSP {header}"),
+        None => println!("          EZSP header truncated"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ashv2::frame::Frame;
+
+    use super::{Format, decode_frames, decode_input};
+
+    const RST: [u8; 5] = [0x1A, 0xC0, 0x38, 0xBC, 0x7E];
+    const RST_HEX: &str = "1A C0 38 BC 7E";
+
+    #[test]
+    fn raw_input_is_passed_through() {
+        let input = RST_HEX.as_bytes().to_vec();
+        assert_eq!(decode_input(input.clone(), Format::Raw), Ok(input));
+    }
+
+    #[test]
+    fn hex_input_is_parsed() {
+        let input = b"# RST\n0x1A, 0xC0\n[38 BC] 7E\n".to_vec();
+        assert_eq!(decode_input(input, Format::Hex), Ok(RST.to_vec()));
+        assert_eq!(
+            decode_input(RST_HEX.as_bytes().to_vec(), Format::Auto),
+            Ok(RST.to_vec())
+        );
+    }
+
+    #[test]
+    fn malformed_hex_is_rejected() {
+        assert_eq!(
+            decode_input(b"1A C0\n38 B 7E".to_vec(), Format::Hex),
+            Err("Invalid hex byte `B` on line 2.".to_owned())
+        );
+        assert_eq!(
+            decode_input(b"1A ZZ".to_vec(), Format::Hex),
+            Err("Invalid hex byte `ZZ` on line 1.".to_owned())
+        );
+        assert!(
+            decode_input(vec![0x1A, 0xFF], Format::Hex)
+                .is_err_and(|error| error.starts_with("Input is not valid UTF-8"))
+        );
+    }
+
+    #[test]
+    fn malformed_hex_falls_back_to_raw_input() {
+        let input = b"1A ZZ".to_vec();
+        assert_eq!(decode_input(input.clone(), Format::Auto), Ok(input));
+        assert_eq!(decode_input(RST.to_vec(), Format::Auto), Ok(RST.to_vec()));
+    }
+
+    #[test]
+    fn partial_trailing_frame_is_returned() {
+        let mut bytes = RST.to_vec();
+        bytes.extend_from_slice(&RST[1..4]);
+
+        let (entries, trailing) = decode_frames(&bytes);
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(entries[0].frame, Ok(Frame::Rst(_))));
+        assert_eq!(trailing, RST[1..4]);
+    }
+
+    #[test]
+    fn complete_frames_leave_no_trailing_bytes() {
+        let (entries, trailing) = decode_frames(&RST);
+        assert_eq!(entries.len(), 1);
+        assert!(trailing.is_empty());
+    }
+}
@@ -0,0 +1,588 @@
+//! Firmware upload to the Gecko bootloader of an NCP.
+//!
+//! After the EZSP command `launchStandaloneBootloader`, the NCP leaves `ASHv2` mode and the Gecko
+//! bootloader takes over the serial link with a text menu:
+//!
+//! ```text
+//! Gecko Bootloader v2.4.1
+//! 1. upload gbl
+//! 2. run
+//! 3. ebl info
+//! BL >
+//! ```
+//!
+//! [`Bootloader`] drives this menu on the same transport that was used for `ASHv2`. It uploads a
+//! `.gbl` image with XMODEM-CRC, i.e. 128-byte blocks protected by a CRC-16/XMODEM checksum,
+//! reporting the [`Progress`] after every acknowledged block. [`Bootloader::run`] then starts the
+//! uploaded application and verifies that the NCP answers an `RST` frame with an `ASHv2`
+//! `RST_ACK` frame, after which the transport can be passed to [`crate::start`] again.
+//!
+//! ```no_run
+//! use ashv2::bootloader::{self, Settings};
+//!
+//! # async fn run(port: tokio::io::DuplexStream, image: Vec<u8>) -> Result<(), bootloader::Error> {
+//! let (port, rst_ack) = bootloader::flash(port, Settings::default(), &image, |progress| {
+//!     println!("{progress}");
+//! })
+//! .await?;
+//! println!("NCP is back in ASHv2 mode: {rst_ack}");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::iter::once;
+use std::time::Duration;
+
+use crc::{CRC_16_XMODEM, Crc};
+use log::{debug, info, trace, warn};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::timeout;
+
+use crate::T_RSTACK_MAX_MILLIS;
+use crate::deframer::Deframer;
+use crate::frame::{Frame, RST, RstAck};
+use crate::protocol::{ControlByte, Stuff};
+use crate::stats::Counters;
+use crate::types::RawFrame;
+use crate::validate::Validate;
+
+/// Prompt of the bootloader menu.
+pub(crate) const PROMPT: &[u8] = b"BL >";
+
+/// Menu option to upload a `.gbl` image.
+const UPLOAD: u8 = b'1';
+
+/// Menu option to run the application.
+const RUN: u8 = b'2';
+
+/// Size of an XMODEM block.
+const BLOCK_SIZE: usize = 128;
+
+/// Byte used to pad the last block of the image.
+const PADDING: u8 = 0xFF;
+
+/// Start of an XMODEM block.
+const SOH: u8 = 0x01;
+
+/// End of the XMODEM transmission.
+const EOT: u8 = 0x04;
+
+/// Positive acknowledgement of an XMODEM block.
+const ACK: u8 = 0x06;
+
+/// Negative acknowledgement of an XMODEM block.
+const NAK: u8 = 0x15;
+
+/// Cancellation of the XMODEM transmission.
+const CAN: u8 = 0x18;
+
+/// Request of the receiver to start an XMODEM transmission with CRC checksums.
+const CRC_REQUEST: u8 = b'C';
+
+/// Checksum of XMODEM-CRC blocks.
+const XMODEM_CRC: Crc<u16> = Crc::<u16>::new(&CRC_16_XMODEM);
+
+/// Size of an unstuffed `RST_ACK` frame.
+const RST_ACK_SIZE: usize = 5;
+
+/// Maximum time to wait for the `RST_ACK` frame after sending an `RST` frame.
+const T_RSTACK_MAX: Duration = Duration::from_millis(T_RSTACK_MAX_MILLIS);
+
+/// Settings of a firmware upload.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Settings {
+    /// Maximum time to wait for a response of the bootloader.
+    pub timeout: Duration,
+
+    /// Number of attempts to transmit a block or reset the application.
+    pub retries: u8,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            retries: 10,
+        }
+    }
+}
+
+/// Progress of a firmware upload.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Progress {
+    /// Number of image bytes acknowledged by the bootloader.
+    pub sent: usize,
+
+    /// Size of the image in bytes.
+    pub total: usize,
+}
+
+impl Display for Progress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{} bytes", self.sent, self.total)
+    }
+}
+
+/// Errors of a firmware upload.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading from or writing to the transport failed.
+    Io(io::Error),
+
+    /// The bootloader did not respond in time.
+    Timeout,
+
+    /// The bootloader cancelled the transmission.
+    Cancelled,
+
+    /// The bootloader rejected the given block too many times.
+    RetriesExceeded {
+        /// Number of the rejected block, starting at 1.
+        block: usize,
+    },
+
+    /// The application did not answer the `RST` frame.
+    NoRstAck,
+
+    /// The application answered with an `RST_ACK` frame of an unsupported ASH version.
+    InvalidRstAck(RstAck),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "I/O error: {error}"),
+            Self::Timeout => write!(f, "Bootloader did not respond in time"),
+            Self::Cancelled => write!(f, "Bootloader cancelled the transmission"),
+            Self::RetriesExceeded { block } => {
+                write!(f, "Bootloader rejected block {block} too many times")
+            }
+            Self::NoRstAck => write!(f, "Application did not acknowledge the reset"),
+            Self::InvalidRstAck(rst_ack) => write!(f, "Invalid RST ACK frame: {rst_ack}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Drives the Gecko bootloader menu on a transport.
+#[derive(Debug)]
+pub struct Bootloader<T> {
+    stream: T,
+    settings: Settings,
+}
+
+impl<T> Bootloader<T> {
+    /// Create a bootloader client on a transport whose NCP runs the Gecko bootloader.
+    #[must_use]
+    pub const fn new(stream: T, settings: Settings) -> Self {
+        Self { stream, settings }
+    }
+
+    /// Return the underlying transport.
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+}
+
+impl<T> Bootloader<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Request the bootloader menu and return its text.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the menu could not be requested or the prompt did not appear in
+    /// time.
+    pub async fn menu(&mut self) -> Result<String, Error> {
+        self.stream.write_all(b"\r\n").await?;
+        self.read_menu().await
+    }
+
+    /// Upload a `.gbl` image and wait for the bootloader menu to reappear.
+    ///
+    /// `progress` is called after every block that the bootloader acknowledged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the upload failed.
+    pub async fn upload<F>(&mut self, image: &[u8], mut progress: F) -> Result<(), Error>
+    where
+        F: FnMut(Progress),
+    {
+        self.stream.write_all(&[UPLOAD]).await?;
+        self.await_crc_request().await?;
+        info!("Uploading {} bytes.", image.len());
+
+        let mut sent = 0;
+
+        for (index, chunk) in image.chunks(BLOCK_SIZE).enumerate() {
+            self.send_block(index + 1, chunk).await?;
+            sent += chunk.len();
+            progress(Progress {
+                sent,
+                total: image.len(),
+            });
+        }
+
+        self.send_eot().await?;
+        self.read_menu().await?;
+        info!("Upload complete.");
+        Ok(())
+    }
+
+    /// Run the application and verify that it speaks `ASHv2`.
+    ///
+    /// Returns the transport and the `RST_ACK` frame of the application.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the application could not be started or did not answer an `RST`
+    /// frame with a valid `RST_ACK` frame.
+    pub async fn run(mut self) -> Result<(T, RstAck), Error> {
+        self.stream.write_all(&[RUN]).await?;
+        let rst_ack = self.reset().await?;
+        Ok((self.stream, rst_ack))
+    }
+
+    /// Read the bootloader output up to the menu prompt.
+    async fn read_menu(&mut self) -> Result<String, Error> {
+        let mut menu = Vec::new();
+
+        while !menu.trim_ascii_end().ends_with(PROMPT) {
+            menu.push(self.read_byte().await?);
+        }
+
+        let menu = String::from_utf8_lossy(&menu).into_owned();
+        debug!("Bootloader menu: {menu}");
+        Ok(menu)
+    }
+
+    /// Wait for the receiver to request an XMODEM-CRC transmission.
+    async fn await_crc_request(&mut self) -> Result<(), Error> {
+        loop {
+            match self.read_byte().await? {
+                CRC_REQUEST => return Ok(()),
+                CAN => return Err(Error::Cancelled),
+                byte => trace!("Ignoring byte before transmission: {byte:#04X}"),
+            }
+        }
+    }
+
+    /// Send a block and wait for its acknowledgement, retransmitting it on `NAK`s and timeouts.
+    async fn send_block(&mut self, number: usize, chunk: &[u8]) -> Result<(), Error> {
+        let block = block(number, chunk);
+
+        for attempt in 1..=self.settings.retries {
+            trace!("Sending block {number}, attempt {attempt}.");
+            self.stream.write_all(&block).await?;
+
+            if self.await_ack().await? {
+                return Ok(());
+            }
+
+            warn!("Bootloader rejected block {number}.");
+        }
+
+        Err(Error::RetriesExceeded { block: number })
+    }
+
+    /// Send the end of transmission and wait for its acknowledgement.
+    async fn send_eot(&mut self) -> Result<(), Error> {
+        for _ in 0..self.settings.retries {
+            self.stream.write_all(&[EOT]).await?;
+
+            if self.await_ack().await? {
+                return Ok(());
+            }
+        }
+
+        Err(Error::Timeout)
+    }
+
+    /// Wait for an `ACK` or `NAK`.
+    ///
+    /// Returns `false` on a `NAK` or if the bootloader did not respond in time.
+    async fn await_ack(&mut self) -> Result<bool, Error> {
+        loop {
+            match self.read_byte().await {
+                Ok(ACK) => return Ok(true),
+                Ok(NAK) | Err(Error::Timeout) => return Ok(false),
+                Ok(CAN) => return Err(Error::Cancelled),
+                Ok(byte) => trace!("Ignoring byte while awaiting ACK: {byte:#04X}"),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Send `RST` frames until the application answers with an `RST_ACK` frame.
+    async fn reset(&mut self) -> Result<RstAck, Error> {
+        let rst = rst();
+
+        for _ in 0..self.settings.retries {
+            self.stream.write_all(&rst).await?;
+
+            if let Ok(rst_ack) = timeout(T_RSTACK_MAX, self.read_rst_ack()).await {
+                let rst_ack = rst_ack?;
+
+                if !rst_ack.is_ash_v2() {
+                    return Err(Error::InvalidRstAck(rst_ack));
+                }
+
+                info!("Application acknowledged reset: {rst_ack}");
+                return Ok(rst_ack);
+            }
+
+            debug!("No RST ACK frame received, resending RST frame.");
+        }
+
+        Err(Error::NoRstAck)
+    }
+
+    /// Read frames until a valid `RST_ACK` frame is received.
+    async fn read_rst_ack(&mut self) -> Result<RstAck, Error> {
+        let counters = Counters::unpublished();
+        let mut deframer = Deframer::new();
+
+        loop {
+            if !deframer.push(self.stream.read_u8().await?, &counters) {
+                continue;
+            }
+
+            if let Some(rst_ack) = rst_ack(deframer.frame()) {
+                return Ok(rst_ack);
+            }
+        }
+    }
+
+    /// Read a byte within the configured timeout.
+    async fn read_byte(&mut self) -> Result<u8, Error> {
+        timeout(self.settings.timeout, self.stream.read_u8())
+            .await
+            .map_err(|_| Error::Timeout)?
+            .map_err(Error::Io)
+    }
+}
+
+/// Upload `image` to the Gecko bootloader, run it and verify that the NCP speaks `ASHv2` again.
+///
+/// Returns the transport and the `RST_ACK` frame of the application.
+///
+/// # Errors
+///
+/// Returns an [`Error`] if any step of the upload failed.
+pub async fn flash<T, F>(
+    stream: T,
+    settings: Settings,
+    image: &[u8],
+    progress: F,
+) -> Result<(T, RstAck), Error>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    F: FnMut(Progress),
+{
+    let mut bootloader = Bootloader::new(stream, settings);
+    bootloader.menu().await?;
+    bootloader.upload(image, progress).await?;
+    bootloader.run().await
+}
+
+/// Returns a `CANCEL` byte followed by a stuffed and terminated `RST` frame.
+pub(crate) fn rst() -> Vec<u8> {
+    let mut rst: RawFrame = RST.into_iter().collect();
+    // The three bytes of an `RST` frame always fit into the buffer after stuffing.
+    let _ = rst.stuff();
+    once(ControlByte::Cancel.into())
+        .chain(rst)
+        .chain(once(ControlByte::Flag.into()))
+        .collect()
+}
+
+/// Parse an unstuffed frame as a valid `RST_ACK` frame.
+pub(crate) fn rst_ack(frame: &[u8]) -> Option<RstAck> {
+    // Text printed while the application starts may precede the first frame.
+    let tail = &frame[frame.len().saturating_sub(RST_ACK_SIZE)..];
+
+    match Frame::try_from(tail) {
+        Ok(Frame::RstAck(rst_ack)) if rst_ack.validate().is_ok() => Some(rst_ack),
+        Ok(frame) => {
+            debug!("Ignoring frame while awaiting RST ACK: {frame}");
+            None
+        }
+        Err(error) => {
+            debug!("Ignoring invalid frame while awaiting RST ACK: {error}");
+            None
+        }
+    }
+}
+
+/// Encode an XMODEM-CRC block.
+fn block(number: usize, chunk: &[u8]) -> Vec<u8> {
+    // Block numbers are transmitted modulo 256.
+    #[expect(clippy::cast_possible_truncation)]
+    let number = number as u8;
+    let mut data = [PADDING; BLOCK_SIZE];
+    data[..chunk.len()].copy_from_slice(chunk);
+    let mut block = Vec::with_capacity(BLOCK_SIZE + 5);
+    block.extend_from_slice(&[SOH, number, !number]);
+    block.extend_from_slice(&data);
+    block.extend_from_slice(&XMODEM_CRC.checksum(&data).to_be_bytes());
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, duplex};
+    use tokio::runtime::{Builder, Runtime};
+
+    use super::{
+        ACK, BLOCK_SIZE, CAN, Error, NAK, PADDING, Progress, SOH, Settings, XMODEM_CRC, flash,
+    };
+    use crate::code::Code;
+    use crate::frame::RstAck;
+    use crate::protocol::Stuff;
+    use crate::types::RawFrame;
+
+    const BUFFER_SIZE: usize = 1024;
+    const MENU: &[u8] =
+        b"\r\nGecko Bootloader v2.4.1\r\n1. upload gbl\r\n2. run\r\n3. ebl info\r\nBL > ";
+
+    fn runtime() -> Runtime {
+        Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("runtime should build")
+    }
+
+    /// Stand-in for the Gecko bootloader that rejects the first block once and returns the
+    /// received image.
+    async fn bootloader(mut stream: DuplexStream) -> Vec<u8> {
+        let mut image = Vec::new();
+        let mut rejected = false;
+
+        loop {
+            match stream.read_u8().await.expect("host should send") {
+                b'\r' => {}
+                b'\n' => stream.write_all(MENU).await.expect("menu should be sent"),
+                b'1' => {
+                    stream
+                        .write_all(b"\r\nbegin upload\r\nCC")
+                        .await
+                        .expect("upload should start");
+
+                    while stream.read_u8().await.expect("host should send") == SOH {
+                        let mut block = [0; BLOCK_SIZE + 4];
+                        stream
+                            .read_exact(&mut block)
+                            .await
+                            .expect("block should be complete");
+                        let (header, rest) = block.split_at(2);
+                        let (data, crc) = rest.split_at(BLOCK_SIZE);
+                        assert_eq!(header[0], !header[1]);
+                        assert_eq!(crc, XMODEM_CRC.checksum(data).to_be_bytes());
+
+                        if rejected {
+                            image.extend_from_slice(data);
+                            stream.write_all(&[ACK]).await.expect("ACK should be sent");
+                        } else {
+                            rejected = true;
+                            stream.write_all(&[NAK]).await.expect("NAK should be sent");
+                        }
+                    }
+
+                    stream.write_all(&[ACK]).await.expect("ACK should be sent");
+                    stream
+                        .write_all(b"\r\nSerial upload complete\r\n")
+                        .await
+                        .expect("completion should be sent");
+                    stream.write_all(MENU).await.expect("menu should be sent");
+                }
+                b'2' => {
+                    while stream.read_u8().await.expect("host should send RST") != 0x7E {}
+
+                    let mut rst_ack: RawFrame = RstAck::new(Code::PowerOn).into_iter().collect();
+                    rst_ack.stuff().expect("frame should fit");
+                    stream
+                        .write_all(&rst_ack)
+                        .await
+                        .expect("RST ACK should be sent");
+                    stream
+                        .write_all(&[0x7E])
+                        .await
+                        .expect("FLAG should be sent");
+                    return image;
+                }
+                byte => panic!("unexpected byte: {byte:#04X}"),
+            }
+        }
+    }
+
+    #[test]
+    fn flash_uploads_image_and_resets_application() {
+        runtime().block_on(async {
+            let (host, ncp) = duplex(BUFFER_SIZE);
+            let stand_in = tokio::spawn(bootloader(ncp));
+            let image: Vec<u8> = (0..=255).chain(0..44).collect();
+            let mut progress = Vec::new();
+
+            let (_host, rst_ack) = flash(host, Settings::default(), &image, |update| {
+                progress.push(update);
+            })
+            .await
+            .expect("image should be flashed");
+
+            assert_eq!(rst_ack.code(), Ok(Code::PowerOn));
+            assert_eq!(
+                progress.last(),
+                Some(&Progress {
+                    sent: image.len(),
+                    total: image.len()
+                })
+            );
+            assert_eq!(progress.len(), 3);
+            let received = stand_in.await.expect("stand-in should finish");
+            assert_eq!(&received[..image.len()], image.as_slice());
+            assert!(received[image.len()..].iter().all(|&byte| byte == PADDING));
+        });
+    }
+
+    #[test]
+    fn cancellation_aborts_upload() {
+        runtime().block_on(async {
+            let (host, mut ncp) = duplex(BUFFER_SIZE);
+            tokio::spawn(async move {
+                ncp.write_all(MENU).await.expect("menu should be sent");
+                ncp.write_all(b"C").await.expect("upload should start");
+                let mut request = [0; 4];
+                ncp.read_exact(&mut request)
+                    .await
+                    .expect("host should request menu, select upload and send block");
+                assert_eq!(request, [b'\r', b'\n', b'1', SOH]);
+                ncp.write_all(&[CAN, CAN])
+                    .await
+                    .expect("CAN should be sent");
+                // Keep the stream open.
+                while ncp.read_u8().await.is_ok() {}
+            });
+
+            let result = flash(host, Settings::default(), &[0x00; 10], |_| {}).await;
+            assert!(matches!(result, Err(Error::Cancelled)), "{result:?}");
+        });
+    }
+}
@@ -5,7 +5,7 @@ use core::fmt::{Display, Formatter};
 use num_derive::FromPrimitive;
 
 /// Reset and error codes.
-#[derive(Clone, Debug, Eq, Hash, PartialEq, FromPrimitive)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, FromPrimitive)]
 #[repr(u8)]
 pub enum Code {
     /// Reset: Unknown reason
@@ -37,7 +37,7 @@ pub enum Code {
 }
 
 impl Display for Code {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::UnknownReason => write!(f, "Reset: Unknown reason"),
             Self::External => write!(f, "Reset: External"),
@@ -0,0 +1,203 @@
+//! Sans-I/O reconstruction of `ASHv2` frames from a serial byte stream.
+//!
+//! The [`Deframer`] applies the `ASHv2` control-byte handling byte by byte and un-stuffs
+//! completed frames. It is shared by the receiver's buffer and by offline replay, so both decode
+//! a byte stream identically.
+
+use log::{debug, trace, warn};
+
+use crate::hex_slice::HexSlice;
+use crate::protocol::{ControlByte, Unstuff};
+use crate::stats::Counters;
+use crate::types::MAX_FRAME_SIZE;
+
+/// Reconstructs frames from serial bytes.
+#[derive(Debug)]
+pub struct Deframer {
+    /// Accumulates the current raw frame until a `FLAG` byte terminates it.
+    frame: Vec<u8>,
+    /// Stuffed bytes of the last completed frame, including the terminating `FLAG` byte.
+    raw: Vec<u8>,
+    /// Whether the current frame was invalidated by a `SUBSTITUTE` byte.
+    error: bool,
+    /// Whether `frame` holds a completed frame that has to be discarded on the next byte.
+    complete: bool,
+}
+
+impl Deframer {
+    /// Create a new deframer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            frame: Vec::with_capacity(MAX_FRAME_SIZE),
+            raw: Vec::with_capacity(MAX_FRAME_SIZE),
+            error: false,
+            complete: false,
+        }
+    }
+
+    /// Process the next byte of the stream.
+    ///
+    /// Returns `true` if the byte completed a frame, which is then available through
+    /// [`Self::frame`] and [`Self::raw`] until the next byte is pushed.
+    pub fn push(&mut self, byte: u8, counters: &Counters) -> bool {
+        if self.complete {
+            self.reset();
+        }
+
+        match ControlByte::try_from(byte) {
+            Ok(control_byte) => match control_byte {
+                ControlByte::Cancel => {
+                    trace!("Resetting buffer due to cancel byte.");
+                    counters.cancels.increment();
+                    self.reset();
+                }
+                ControlByte::Flag => {
+                    trace!("Received flag byte.");
+
+                    if !self.error && !self.frame.is_empty() {
+                        debug!("Received frame.");
+                        trace!("Buffer: {:#04X}", HexSlice::new(&self.frame));
+                        self.raw.clear();
+                        self.raw.extend_from_slice(&self.frame);
+                        self.raw.push(byte);
+                        let stuffed_size = self.frame.len();
+                        self.frame.unstuff();
+                        counters
+                            .stuffing_overhead_received
+                            .add(stuffed_size - self.frame.len());
+                        trace!("Unstuffed buffer: {:#04X}", HexSlice::new(&self.frame));
+                        self.warn_if_frame_exceeds_max_frame_size();
+                        self.complete = true;
+                        return true;
+                    }
+
+                    trace!("Resetting buffer due to error or empty buffer.");
+                    trace!("Error condition was: {}", self.error);
+                    trace!("Buffer: {:#04X}", HexSlice::new(&self.frame));
+                    self.reset();
+                }
+                ControlByte::Substitute => {
+                    trace!("Received SUBSTITUTE byte. Setting error condition.");
+                    counters.substitutes.increment();
+                    self.error = true;
+                }
+                ControlByte::Xon => {
+                    trace!("NCP requested to resume transmission.");
+                }
+                ControlByte::Xoff => {
+                    trace!("NCP requested to stop transmission.");
+                }
+                ControlByte::Wake => {
+                    if self.frame.is_empty() {
+                        debug!("NCP tried to wake us up.");
+                    } else {
+                        self.frame.push(control_byte.into());
+                    }
+                }
+            },
+            Err(byte) => {
+                self.frame.push(byte);
+            }
+        }
+
+        false
+    }
+
+    /// Return the un-stuffed bytes of the completed frame without the terminating `FLAG` byte.
+    ///
+    /// If no frame has been completed by the last pushed byte, this returns the stuffed bytes
+    /// of the incomplete frame.
+    #[must_use]
+    pub fn frame(&self) -> &[u8] {
+        &self.frame
+    }
+
+    /// Return the stuffed bytes received since the last completed frame.
+    #[must_use]
+    pub fn pending(&self) -> &[u8] {
+        if self.complete { &[] } else { &self.frame }
+    }
+
+    /// Return the stuffed bytes of the last completed frame, including the terminating `FLAG`
+    /// byte.
+    #[must_use]
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Discard the current frame.
+    pub fn reset(&mut self) {
+        self.frame.clear();
+        self.frame.shrink_to(MAX_FRAME_SIZE);
+        self.error = false;
+        self.complete = false;
+    }
+
+    /// Warn if the current frame exceeds the maximum frame size.
+    pub fn warn_if_frame_exceeds_max_frame_size(&self) {
+        if self.frame.len() > MAX_FRAME_SIZE {
+            warn!("Receiver frame buffer exceeded maximum frame size of {MAX_FRAME_SIZE} bytes.");
+        }
+    }
+}
+
+impl Default for Deframer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ControlByte, Counters, Deframer, MAX_FRAME_SIZE};
+
+    const FIRST_FRAME_BYTE: u8 = 0x01;
+    const SECOND_FRAME_BYTE: u8 = 0x02;
+
+    fn frames(deframer: &mut Deframer, input: &[u8]) -> Vec<Vec<u8>> {
+        let counters = Counters::unpublished();
+        let mut frames = Vec::new();
+
+        for &byte in input {
+            if deframer.push(byte, &counters) {
+                frames.push(deframer.frame().to_vec());
+            }
+        }
+
+        frames
+    }
+
+    #[test]
+    fn push_discards_completed_frame_and_shrinks() {
+        let flag = u8::from(ControlByte::Flag);
+        let mut input = vec![FIRST_FRAME_BYTE; MAX_FRAME_SIZE + 1];
+        input.push(flag);
+        input.push(SECOND_FRAME_BYTE);
+        input.push(flag);
+        let mut deframer = Deframer::new();
+
+        let frames = frames(&mut deframer, &input);
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].len(), MAX_FRAME_SIZE + 1);
+        assert_eq!(frames[1], [SECOND_FRAME_BYTE]);
+        assert!(deframer.frame.capacity() <= MAX_FRAME_SIZE);
+    }
+
+    #[test]
+    fn push_drops_frames_with_substitute_byte() {
+        let flag = u8::from(ControlByte::Flag);
+        let input = [
+            FIRST_FRAME_BYTE,
+            ControlByte::Substitute.into(),
+            flag,
+            SECOND_FRAME_BYTE,
+            flag,
+        ];
+        let mut deframer = Deframer::new();
+
+        assert_eq!(frames(&mut deframer, &input), [[SECOND_FRAME_BYTE]]);
+        assert_eq!(deframer.raw(), [SECOND_FRAME_BYTE, flag]);
+    }
+}
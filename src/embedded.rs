@@ -0,0 +1,17 @@
+//! Frame I/O over transports implementing the `embedded-io-async` traits.
+//!
+//! This module is available with the `embedded-io-async` crate feature and does not require the
+//! standard library or an allocator. [`Reader`] reconstructs typed [`Frame`](crate::frame::Frame)
+//! values from an [`embedded_io_async::Read`] implementation and [`Writer`] writes stuffed and
+//! terminated frames to an [`embedded_io_async::Write`] implementation.
+//!
+//! Connection handling, i.e. the reset handshake, acknowledgements and retransmissions, is left
+//! to the caller.
+
+pub use self::error::Error;
+pub use self::reader::Reader;
+pub use self::writer::Writer;
+
+mod error;
+mod reader;
+mod writer;
@@ -0,0 +1,53 @@
+//! Errors of the `embedded-io-async` frame reader and writer.
+
+use core::fmt::{Debug, Display, Formatter};
+
+use crate::frame::DecodeError;
+
+/// Errors that can occur when reading or writing frames over `embedded-io-async` transports.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Error<E> {
+    /// The underlying transport failed.
+    Io(E),
+
+    /// The underlying transport reached the end of its stream.
+    UnexpectedEof,
+
+    /// A completed frame could not be decoded.
+    Decode(DecodeError),
+
+    /// The given byte did not fit into the frame buffer.
+    BufferOverflow(u8),
+}
+
+impl<E> Display for Error<E>
+where
+    E: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "I/O error: {error:?}"),
+            Self::UnexpectedEof => write!(f, "Unexpected end of stream."),
+            Self::Decode(error) => Display::fmt(error, f),
+            Self::BufferOverflow(byte) => write!(f, "Frame buffer overflow: {byte:#04X}"),
+        }
+    }
+}
+
+impl<E> core::error::Error for Error<E>
+where
+    E: embedded_io_async::Error,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Decode(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl<E> From<DecodeError> for Error<E> {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
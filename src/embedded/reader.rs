@@ -0,0 +1,160 @@
+//! Receive-side frame reader for `embedded-io-async` transports.
+
+use embedded_io_async::Read;
+use log::{debug, trace, warn};
+
+use super::Error;
+use crate::frame::Frame;
+use crate::protocol::{ControlByte, Unstuff};
+use crate::types::RawFrame;
+
+/// Amount of bytes read from the transport at once.
+const CHUNK_SIZE: usize = 32;
+
+/// Reads `ASHv2` frames from an `embedded-io-async` transport.
+///
+/// Bytes read after a completed frame are retained for the next call to
+/// [`read_frame()`](Self::read_frame).
+#[derive(Debug)]
+pub struct Reader<T> {
+    /// The underlying transport.
+    inner: T,
+    /// Chunk of bytes read from the transport.
+    chunk: [u8; CHUNK_SIZE],
+    /// Index of the next unconsumed byte in `chunk`.
+    position: usize,
+    /// Amount of valid bytes in `chunk`.
+    length: usize,
+    /// Accumulates the current raw frame until a `FLAG` byte terminates it.
+    frame: RawFrame,
+}
+
+impl<T> Reader<T> {
+    /// Create a new frame reader around a transport.
+    #[must_use]
+    pub const fn new(inner: T) -> Self {
+        Self {
+            inner,
+            chunk: [0; CHUNK_SIZE],
+            position: 0,
+            length: 0,
+            frame: RawFrame::new(),
+        }
+    }
+
+    /// Return the underlying transport.
+    ///
+    /// Any buffered bytes that have not yet been consumed are discarded.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> Reader<T>
+where
+    T: Read,
+{
+    /// Read the next complete `ASHv2` [`Frame`].
+    ///
+    /// The method waits until a complete frame is terminated by `FLAG`, then applies byte
+    /// unstuffing and frame parsing before returning. Frames that exceed the frame buffer or
+    /// that were marked as erroneous by a `SUBSTITUTE` byte are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transport fails, the transport reaches the end of its stream or
+    /// the completed frame cannot be decoded.
+    pub async fn read_frame(&mut self) -> Result<Frame, Error<T::Error>> {
+        self.frame.clear();
+        let mut error = false;
+
+        loop {
+            let byte = self.next_byte().await?;
+
+            match ControlByte::try_from(byte) {
+                Ok(ControlByte::Cancel) => {
+                    trace!("Resetting buffer due to cancel byte.");
+                    self.frame.clear();
+                    error = false;
+                }
+                Ok(ControlByte::Flag) => {
+                    if !error && !self.frame.is_empty() {
+                        debug!("Received frame.");
+                        self.frame.unstuff();
+                        return Ok(Frame::try_from(self.frame.as_slice())?);
+                    }
+
+                    trace!("Resetting buffer due to error or empty buffer.");
+                    self.frame.clear();
+                    error = false;
+                }
+                Ok(ControlByte::Substitute) => {
+                    trace!("Received SUBSTITUTE byte. Setting error condition.");
+                    error = true;
+                }
+                Ok(ControlByte::Xon) => {
+                    trace!("NCP requested to resume transmission.");
+                }
+                Ok(ControlByte::Xoff) => {
+                    trace!("NCP requested to stop transmission.");
+                }
+                Ok(ControlByte::Wake) if self.frame.is_empty() => {
+                    debug!("NCP tried to wake us up.");
+                }
+                Ok(ControlByte::Wake) | Err(_) => {
+                    if self.frame.push(byte).is_err() {
+                        warn!("Frame buffer overflow. Discarding frame.");
+                        error = true;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn next_byte(&mut self) -> Result<u8, Error<T::Error>> {
+        if self.position >= self.length {
+            self.length = self.inner.read(&mut self.chunk).await.map_err(Error::Io)?;
+            self.position = 0;
+
+            if self.length == 0 {
+                return Err(Error::UnexpectedEof);
+            }
+        }
+
+        let byte = self.chunk[self.position];
+        self.position += 1;
+        Ok(byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::runtime::Builder;
+
+    use super::{Error, Reader};
+    use crate::frame::{Ack, Frame, RST};
+
+    #[test]
+    fn read_frame_handles_control_bytes() {
+        Builder::new_current_thread()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let input: &[u8] = &[
+                    // Cancelled garbage followed by an RST frame.
+                    0x12, 0x1A, 0xC0, 0x38, 0xBC, 0x7E,
+                    // Substituted frame that is discarded.
+                    0x81, 0x18, 0x59, 0x7E, // XON, XOFF and an ACK frame.
+                    0x11, 0x13, 0x81, 0x60, 0x59, 0x7E,
+                ];
+                let mut reader = Reader::new(input);
+
+                assert_eq!(reader.read_frame().await, Ok(Frame::Rst(RST)));
+                assert_eq!(
+                    reader.read_frame().await,
+                    Ok(Frame::Ack(Ack::new(1, false)))
+                );
+                assert_eq!(reader.read_frame().await, Err(Error::UnexpectedEof));
+            });
+    }
+}
@@ -0,0 +1,96 @@
+//! Transmit-side frame writer for `embedded-io-async` transports.
+
+use core::fmt::Display;
+
+use embedded_io_async::Write;
+use log::debug;
+
+use super::Error;
+use crate::protocol::{ControlByte, Stuff};
+use crate::types::RawFrame;
+
+/// Writes stuffed and terminated `ASHv2` frames to an `embedded-io-async` transport.
+#[derive(Debug)]
+pub struct Writer<T> {
+    /// The underlying transport.
+    inner: T,
+    /// Reusable frame buffer used for stuffing and termination.
+    frame: RawFrame,
+}
+
+impl<T> Writer<T> {
+    /// Create a new frame writer around a transport.
+    #[must_use]
+    pub const fn new(inner: T) -> Self {
+        Self {
+            inner,
+            frame: RawFrame::new(),
+        }
+    }
+
+    /// Return the underlying transport.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> Writer<T>
+where
+    T: Write,
+{
+    /// Write an `ASHv2` frame to the transport.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the write operation failed or the frame buffer overflowed.
+    pub async fn write_frame<F>(&mut self, frame: F) -> Result<(), Error<T::Error>>
+    where
+        F: IntoIterator<Item = u8> + Display,
+    {
+        debug!("Writing frame: {frame}");
+        self.frame.clear();
+
+        for byte in frame {
+            self.frame.push(byte).map_err(Error::BufferOverflow)?;
+        }
+
+        self.frame.stuff().map_err(Error::BufferOverflow)?;
+        self.frame
+            .push(ControlByte::Flag.into())
+            .map_err(Error::BufferOverflow)?;
+        self.inner.write_all(&self.frame).await.map_err(Error::Io)?;
+        self.inner.flush().await.map_err(Error::Io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::runtime::Builder;
+
+    use super::Writer;
+    use crate::frame::{Ack, RST};
+
+    #[test]
+    fn write_frame_stuffs_and_terminates() {
+        Builder::new_current_thread()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let mut output = [0; 9];
+                let mut writer = Writer::new(output.as_mut_slice());
+                writer
+                    .write_frame(RST)
+                    .await
+                    .expect("RST should be written");
+                // The CRC of ACK(3)- is 0xC113, whose second byte is XOFF and must be escaped.
+                writer
+                    .write_frame(Ack::new(3, true))
+                    .await
+                    .expect("ACK should be written");
+                assert_eq!(
+                    output,
+                    [0xC0, 0x38, 0xBC, 0x7E, 0x8B, 0xC1, 0x7D, 0x33, 0x7E]
+                );
+            });
+    }
+}
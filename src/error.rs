@@ -0,0 +1,111 @@
+//! Errors of the `ASHv2` actor.
+
+use core::fmt::{Display, Formatter};
+use std::io;
+
+/// Errors that can occur when sending payloads through the `ASHv2` actor.
+#[derive(Debug)]
+#[expect(variant_size_differences)]
+pub enum Error {
+    /// The actor futures have terminated and no longer accept messages.
+    ActorShutDown,
+
+    /// A `DATA` frame was not acknowledged within the maximum number of retransmissions.
+    RetransmissionLimitExceeded {
+        /// The frame number of the unacknowledged `DATA` frame.
+        frame_num: u8,
+    },
+
+    /// The queue of unacknowledged transmissions is full.
+    TransmissionQueueFull,
+
+    /// The given byte did not fit into the frame buffer.
+    BufferOverflow(u8),
+
+    /// Writing to the underlying transport failed.
+    Io(io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::ActorShutDown => write!(f, "ASHv2 actor has shut down."),
+            Self::RetransmissionLimitExceeded { frame_num } => {
+                write!(f, "Retransmission limit of frame #{frame_num} exceeded.")
+            }
+            Self::TransmissionQueueFull => write!(f, "Transmission queue is full."),
+            Self::BufferOverflow(byte) => write!(f, "Frame buffer overflow: {byte:#04X}"),
+            Self::Io(error) => write!(f, "I/O error: {error}"),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        use io::ErrorKind;
+
+        let kind = match error {
+            Error::Io(error) => return error,
+            Error::ActorShutDown => ErrorKind::BrokenPipe,
+            Error::RetransmissionLimitExceeded { .. } => ErrorKind::TimedOut,
+            Error::TransmissionQueueFull => ErrorKind::OutOfMemory,
+            Error::BufferOverflow(_) => ErrorKind::InvalidData,
+        };
+
+        Self::new(kind, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::error::Error as _;
+    use std::io::{self, ErrorKind};
+
+    use super::Error;
+
+    #[test]
+    fn io_error_is_source() {
+        let error = Error::from(io::Error::from(ErrorKind::BrokenPipe));
+        let source = error
+            .source()
+            .and_then(|source| source.downcast_ref::<io::Error>())
+            .expect("I/O error should be the source");
+        assert_eq!(source.kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn into_io_error_unwraps_io_error() {
+        let error = io::Error::from(Error::Io(io::Error::from(ErrorKind::WriteZero)));
+        assert_eq!(error.kind(), ErrorKind::WriteZero);
+        assert!(error.get_ref().is_none());
+    }
+
+    #[test]
+    fn into_io_error_maps_kind() {
+        let error = io::Error::from(Error::RetransmissionLimitExceeded { frame_num: 3 });
+        assert_eq!(error.kind(), ErrorKind::TimedOut);
+        assert_eq!(
+            error.to_string(),
+            "Retransmission limit of frame #3 exceeded."
+        );
+        assert_eq!(
+            io::Error::from(Error::ActorShutDown).kind(),
+            ErrorKind::BrokenPipe
+        );
+    }
+}
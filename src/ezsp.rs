@@ -1,12 +1,33 @@
 //! Optional adapters between the `ASHv2` payload API and typed EZSP frames.
 //!
-//! This module is available with the `ezsp` crate feature. [`Transmitter`] aliases
-//! [`crate::Handle`], which implements `ezsp::Transmit`. [`Receiver`] consumes the inbound
+//! This module is available with the `ezsp` crate feature. [`crate::Handle`] implements
+//! `ezsp::Transmit` and sends frames unchanged, while [`Transmitter`] wraps a handle and adapts
+//! frame headers to a [`NegotiatedVersion`]. [`Receiver`] consumes the inbound
 //! [`crate::Payload`] channel and implements `ezsp::Receive`; the negotiated EZSP version is
 //! supplied to each receive call by the EZSP layer.
+//! [`Receiver::receive_result`] additionally yields payloads that fail to decode as a
+//! [`DecodeError`] instead of discarding them.
+//!
+//! [`split`] routes the inbound payloads into separate [`Receiver`]s for command responses and
+//! callbacks, so that callback handlers can run independently of request/response code.
+//!
+//! [`Client`] assigns EZSP sequence numbers to commands and matches the responses to them, so
+//! that several commands can be outstanding at the same time. Outstanding commands fail when
+//! their response times out or cannot be decoded, or when the NCP is reset.
+//!
+//! [`negotiate`] performs the EZSP `version` exchange through a [`Client`] each time the `ASHv2`
+//! connection is established and stores the result in a [`NegotiatedVersion`]. A [`Transmitter`]
+//! and a [`Receiver`] that share this version select legacy or extended frame headers
+//! automatically.
 
-pub use self::receiver::Receiver;
+pub use self::client::Client;
+pub use self::negotiation::{NegotiatedVersion, negotiate};
+pub use self::receiver::{DecodeError, Receiver};
+pub use self::split::split;
 pub use self::transmitter::Transmitter;
 
+mod client;
+mod negotiation;
 mod receiver;
+mod split;
 mod transmitter;
@@ -0,0 +1,589 @@
+use std::collections::BTreeMap;
+use std::future::poll_fn;
+use std::io::{self, ErrorKind};
+use std::pin::pin;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::atomic::{AtomicU8, AtomicU64};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::Poll;
+use std::time::Duration;
+
+use ezsp::{Command, Commands, Error, Extended, Frame, Header, Legacy, Parameters, ValueError};
+use log::{debug, trace, warn};
+use tokio::sync::{oneshot, watch};
+use tokio::time::timeout;
+
+use super::transmitter::WeakTransmitter;
+use super::{DecodeError, NegotiatedVersion, Receiver, Transmitter};
+
+/// EZSP client that correlates command responses by their sequence number.
+///
+/// The client assigns a sequence number to each command, sends it through a [`Transmitter`]
+/// and waits for the response with the same sequence number and frame ID. Several commands may
+/// be outstanding at the same time, e.g. when the client is cloned and used from concurrent
+/// tasks. Each command fails if its response does not arrive within the client's timeout.
+///
+/// A response that cannot be decoded fails the command with the same sequence number with the
+/// decoding error, e.g. [`Error::InvalidCommand`] if the NCP rejected the command.
+///
+/// When the NCP is reset, all commands that have been sent but not yet answered fail with an
+/// [`Error::Io`] of kind [`ErrorKind::ConnectionReset`], since the NCP will never respond to them.
+#[derive(Clone, Debug)]
+pub struct Client {
+    transmitter: Transmitter,
+    timeout: Duration,
+    shared: Arc<Shared>,
+}
+
+impl Client {
+    /// Create a client sending commands through `transmitter` and reading responses from
+    /// `responses`.
+    ///
+    /// `responses` should yield command responses only, e.g. the response receiver returned by
+    /// [`split`](super::split). Callbacks read from it are discarded.
+    ///
+    /// Returns the client and the dispatcher future that the caller must spawn or otherwise poll
+    /// on their async runtime. The dispatcher terminates when `responses` closes, after which
+    /// every outstanding and subsequent command fails.
+    ///
+    /// The client shares the [`NegotiatedVersion`] of `transmitter`.
+    pub fn new(
+        transmitter: Transmitter,
+        responses: Receiver,
+        timeout: Duration,
+    ) -> (Self, impl Future<Output = ()> + Send + 'static) {
+        let shared = Arc::new(Shared {
+            version: transmitter.negotiated_version().clone(),
+            sequence: AtomicU8::default(),
+            tickets: AtomicU64::default(),
+            connection: AtomicU64::default(),
+            pending: Mutex::default(),
+        });
+        let connections = transmitter.handle().connections();
+        let dispatcher = dispatch(responses, connections, shared.clone());
+        (
+            Self {
+                transmitter,
+                timeout,
+                shared,
+            },
+            dispatcher,
+        )
+    }
+
+    /// Return the negotiated EZSP protocol version, if it has been set.
+    #[must_use]
+    pub fn negotiated_version(&self) -> Option<u8> {
+        self.shared.version.get()
+    }
+
+    /// Set the negotiated EZSP protocol version.
+    ///
+    /// The version selects between legacy and extended frame headers for subsequent commands and
+    /// responses of this client and all of its clones.
+    pub fn set_negotiated_version(&self, version: u8) {
+        self.shared.version.set(Some(version));
+    }
+
+    /// Send `command` and wait for its response using the client's timeout.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the command could not be sent, the response did not arrive in
+    /// time, the NCP was reset before it responded, or the NCP responded with another frame ID.
+    pub async fn request<T>(&self, command: T) -> Result<Parameters, Error>
+    where
+        T: Into<Commands>,
+    {
+        self.request_with_timeout(command, self.timeout).await
+    }
+
+    /// Send `command` and wait at most `duration` for its response.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the command could not be sent, the command was not sent and
+    /// answered in time, the NCP was reset before it responded, or the NCP responded with another
+    /// frame ID.
+    pub async fn request_with_timeout<T>(
+        &self,
+        command: T,
+        duration: Duration,
+    ) -> Result<Parameters, Error>
+    where
+        T: Into<Commands>,
+    {
+        let command = command.into();
+        let id = command.id();
+        let (registration, response) = self.shared.register(id)?;
+        let sequence = registration.sequence;
+        let header = header(sequence, id, &self.shared.version)?;
+        trace!("Sending command #{sequence} with ID {id:#06X}.");
+
+        // The timeout also covers holding the command back until the version is negotiated.
+        let exchange = async {
+            let connection = self
+                .transmitter
+                .send_frame(Frame::new(header, command))
+                .await?;
+            self.shared.transmitted(sequence, connection);
+            response.await?
+        };
+
+        timeout(duration, exchange).await.map_err(|_| {
+            debug!("Command #{sequence} with ID {id:#06X} timed out.");
+            io::Error::from(ErrorKind::TimedOut)
+        })?
+    }
+
+    /// Return the shared negotiated version of the client.
+    pub(crate) fn version(&self) -> &NegotiatedVersion {
+        &self.shared.version
+    }
+
+    /// Subscribe to the number of established connections of the underlying actor.
+    pub(crate) fn connections(&self) -> watch::Receiver<u64> {
+        self.transmitter.handle().connections()
+    }
+
+    /// Create a reference to the client that does not keep the actor alive.
+    pub(crate) fn downgrade(&self) -> WeakClient {
+        WeakClient {
+            transmitter: self.transmitter.downgrade(),
+            timeout: self.timeout,
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// Reference to a [`Client`] that does not keep the actor alive.
+#[derive(Debug)]
+pub struct WeakClient {
+    transmitter: WeakTransmitter,
+    timeout: Duration,
+    shared: Arc<Shared>,
+}
+
+impl WeakClient {
+    /// Return the client, unless every handle to the actor has been dropped.
+    pub fn upgrade(&self) -> Option<Client> {
+        self.transmitter.upgrade().map(|transmitter| Client {
+            transmitter,
+            timeout: self.timeout,
+            shared: self.shared.clone(),
+        })
+    }
+
+    /// Return the shared negotiated version of the client.
+    pub fn version(&self) -> &NegotiatedVersion {
+        &self.shared.version
+    }
+}
+
+/// State shared between the clients and the dispatcher.
+#[derive(Debug)]
+struct Shared {
+    version: NegotiatedVersion,
+    sequence: AtomicU8,
+    tickets: AtomicU64,
+    /// Latest connection observed by the dispatcher, updated while `pending` is locked.
+    connection: AtomicU64,
+    pending: Mutex<BTreeMap<u8, Pending>>,
+}
+
+impl Shared {
+    fn pending(&self) -> MutexGuard<'_, BTreeMap<u8, Pending>> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Reserve a free sequence number for a command with frame ID `id`.
+    fn register(&self, id: u16) -> Result<(Registration<'_>, ResponseReceiver), Error> {
+        let mut pending = self.pending();
+
+        for _ in 0..=u8::MAX {
+            let sequence = self.sequence.fetch_add(1, Relaxed);
+
+            if pending.contains_key(&sequence) {
+                continue;
+            }
+
+            let ticket = self.tickets.fetch_add(1, Relaxed);
+            let (response, receiver) = oneshot::channel();
+            pending.insert(
+                sequence,
+                Pending {
+                    id,
+                    ticket,
+                    connection: None,
+                    response,
+                },
+            );
+            drop(pending);
+            let registration = Registration {
+                shared: self,
+                sequence,
+                ticket,
+            };
+            return Ok((registration, receiver));
+        }
+
+        Err(Error::TransactionQueueFull)
+    }
+
+    /// Record that the command with `sequence` has been sent during `connection`.
+    ///
+    /// If the dispatcher has already observed a later connection, the command is failed
+    /// immediately, since the reset it handled could not include it.
+    fn transmitted(&self, sequence: u8, connection: u64) {
+        let mut pending = self.pending();
+
+        if connection >= self.connection.load(Relaxed) {
+            if let Some(pending) = pending.get_mut(&sequence) {
+                pending.connection.replace(connection);
+            }
+        } else if let Some(stale) = pending.remove(&sequence) {
+            drop(pending);
+            stale.reset(sequence);
+        }
+    }
+
+    /// Complete the command matching the header of `frame`.
+    fn complete(&self, frame: Frame<Parameters>) {
+        let (header, parameters) = frame.into();
+
+        if header.is_async_callback() {
+            debug!("Discarding callback: {header}");
+            return;
+        }
+
+        let Some(pending) = self.pending().remove(&header.sequence()) else {
+            warn!("Discarding response without pending command: {header}");
+            return;
+        };
+
+        let result = if pending.id == header.id() {
+            Ok(parameters)
+        } else {
+            warn!(
+                "Response #{} has ID {:#06X}, expected {:#06X}.",
+                header.sequence(),
+                header.id(),
+                pending.id
+            );
+            Err(Error::UnexpectedResponse(Box::new(parameters)))
+        };
+
+        pending.response.send(result).unwrap_or_else(drop);
+    }
+
+    /// Fail the command matching the header of a response that could not be decoded.
+    fn fail(&self, error: DecodeError) {
+        warn!("{error}");
+
+        let Some(header) = error.header().filter(|header| !header.is_async_callback()) else {
+            return;
+        };
+
+        let pending = self.pending().remove(&header.sequence());
+
+        if let Some(pending) = pending {
+            pending
+                .response
+                .send(Err(error.into_error()))
+                .unwrap_or_else(drop);
+        }
+    }
+
+    /// Fail all commands that have been sent before `connection` was established.
+    fn reset(&self, connection: u64) {
+        let mut pending = self.pending();
+        self.connection.store(connection, Relaxed);
+        let stale: Vec<u8> = pending
+            .iter()
+            .filter(|(_, pending)| pending.connection.is_some_and(|sent| sent < connection))
+            .map(|(&sequence, _)| sequence)
+            .collect();
+
+        for sequence in stale {
+            if let Some(pending) = pending.remove(&sequence) {
+                pending.reset(sequence);
+            }
+        }
+    }
+
+    /// Fail all outstanding commands.
+    fn close(&self) {
+        self.pending().clear();
+    }
+}
+
+/// Receiver of the response to a command.
+type ResponseReceiver = oneshot::Receiver<Result<Parameters, Error>>;
+
+/// A command awaiting its response.
+#[derive(Debug)]
+struct Pending {
+    id: u16,
+    ticket: u64,
+    connection: Option<u64>,
+    response: oneshot::Sender<Result<Parameters, Error>>,
+}
+
+impl Pending {
+    /// Fail the command with `sequence` because the NCP has been reset.
+    fn reset(self, sequence: u8) {
+        debug!("Failing command #{sequence}, the NCP has been reset.");
+        self.response
+            .send(Err(io::Error::from(ErrorKind::ConnectionReset).into()))
+            .unwrap_or_else(drop);
+    }
+}
+
+/// Reservation of a sequence number that is released when the request completes or is dropped.
+struct Registration<'shared> {
+    shared: &'shared Shared,
+    sequence: u8,
+    ticket: u64,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let mut pending = self.shared.pending();
+
+        if pending
+            .get(&self.sequence)
+            .is_some_and(|pending| pending.ticket == self.ticket)
+        {
+            pending.remove(&self.sequence);
+        }
+    }
+}
+
+/// Return the header for a command with `sequence` and frame ID `id`.
+fn header(sequence: u8, id: u16, version: &NegotiatedVersion) -> Result<Header, Error> {
+    if version.is_extended() {
+        Ok(Header::Extended(Extended::new(
+            sequence,
+            Command::default().into(),
+            id,
+        )))
+    } else {
+        let id = id.try_into().map_err(ValueError::InvalidFrameId)?;
+        Ok(Header::Legacy(Legacy::new(
+            sequence,
+            Command::default().into(),
+            id,
+        )))
+    }
+}
+
+/// Dispatch responses to their commands and fail outstanding commands on NCP resets.
+async fn dispatch(
+    mut responses: Receiver,
+    mut connections: watch::Receiver<u64>,
+    shared: Arc<Shared>,
+) {
+    let mut watching = true;
+
+    loop {
+        let negotiated_version = shared.version.get();
+        let event = {
+            let mut response = pin!(responses.receive_result(negotiated_version));
+            let mut changed = pin!(connections.changed());
+
+            poll_fn(|context| {
+                if watching && let Poll::Ready(result) = changed.as_mut().poll(context) {
+                    return Poll::Ready(Event::Connected(result.is_ok()));
+                }
+
+                response.as_mut().poll(context).map(Event::Response)
+            })
+            .await
+        };
+
+        match event {
+            Event::Connected(true) => shared.reset(*connections.borrow_and_update()),
+            Event::Connected(false) => watching = false,
+            Event::Response(Some(Ok(frame))) => shared.complete(frame),
+            Event::Response(Some(Err(error))) => shared.fail(error),
+            Event::Response(None) => break,
+        }
+    }
+
+    shared.close();
+    debug!("EZSP client dispatcher terminated.");
+}
+
+/// Event observed by the dispatcher.
+enum Event {
+    Connected(bool),
+    Response(Option<Result<Frame<Parameters>, DecodeError>>),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicU8, AtomicU64};
+    use std::time::Duration;
+
+    use ezsp::parameters::configuration::{self, version};
+    use ezsp::{Error, Parameters, Response};
+    use tokio::runtime::Builder;
+
+    use super::{Client, Shared};
+    use crate::Payload;
+    use crate::code::Code;
+    use crate::ezsp::{NegotiatedVersion, Transmitter};
+    use crate::virtual_ncp::{self, VirtualNcp};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Start a host and an NCP connected to each other and a client on the host.
+    fn start() -> (Client, VirtualNcp) {
+        let (host, host_rx, ncp) = virtual_ncp::connect();
+        let (client, dispatcher) = Client::new(
+            Transmitter::new(host, NegotiatedVersion::new()),
+            host_rx.into(),
+            TIMEOUT,
+        );
+        tokio::spawn(dispatcher);
+        (client, ncp)
+    }
+
+    /// Legacy `version` response echoing the desired version of the legacy `version` command.
+    fn version_response(command: &Payload) -> Payload {
+        [command[0], 0x80, 0x00, command[3], 0x02, 0x00, 0x74]
+            .into_iter()
+            .collect()
+    }
+
+    fn protocol_version(parameters: Parameters) -> u8 {
+        let Parameters::Response(Response::Configuration(configuration::Response::Version(
+            response,
+        ))) = parameters
+        else {
+            panic!("response should be a version response: {parameters:?}");
+        };
+
+        response.protocol_version()
+    }
+
+    #[test]
+    fn client_correlates_concurrent_responses() {
+        Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (client, mut ncp) = start();
+                let first = tokio::spawn({
+                    let client = client.clone();
+                    async move { client.request(version::Command::new(4)).await }
+                });
+                let second = tokio::spawn({
+                    let client = client.clone();
+                    async move { client.request(version::Command::new(5)).await }
+                });
+
+                let mut commands = Vec::new();
+                commands.push(ncp.receive().await.expect("NCP should receive command"));
+                commands.push(ncp.receive().await.expect("NCP should receive command"));
+                assert_ne!(commands[0][0], commands[1][0]);
+
+                // Answer in reverse order.
+                for command in commands.iter().rev() {
+                    ncp.send(version_response(command))
+                        .await
+                        .expect("NCP should send response");
+                }
+
+                let first = first.await.expect("task should complete");
+                let second = second.await.expect("task should complete");
+                assert_eq!(protocol_version(first.expect("request should succeed")), 4);
+                assert_eq!(protocol_version(second.expect("request should succeed")), 5);
+            });
+    }
+
+    #[test]
+    fn client_fails_commands_on_timeout_and_reset() {
+        Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (client, mut ncp) = start();
+
+                let result = client
+                    .request_with_timeout(version::Command::new(4), Duration::from_millis(50))
+                    .await;
+                assert!(
+                    matches!(result, Err(Error::Io(ref error)) if error.kind() == ErrorKind::TimedOut),
+                    "request should time out: {result:?}"
+                );
+                ncp.receive().await.expect("NCP should receive command");
+
+                let pending = tokio::spawn({
+                    let client = client.clone();
+                    async move { client.request(version::Command::new(4)).await }
+                });
+                ncp.receive().await.expect("NCP should receive command");
+                ncp.send_error(Code::ExceededMaximumAckTimeoutCount)
+                    .await
+                    .expect("NCP should send ERROR");
+
+                let result = pending.await.expect("task should complete");
+                assert!(
+                    matches!(result, Err(Error::Io(ref error)) if error.kind() == ErrorKind::ConnectionReset),
+                    "request should fail on reset: {result:?}"
+                );
+            });
+    }
+
+    #[test]
+    fn client_fails_command_with_decode_error() {
+        Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (client, mut ncp) = start();
+                let pending = tokio::spawn({
+                    let client = client.clone();
+                    async move { client.request(version::Command::new(4)).await }
+                });
+
+                let command = ncp.receive().await.expect("NCP should receive command");
+                ncp.send([command[0], 0x80, 0x58, 0x37].into_iter().collect())
+                    .await
+                    .expect("NCP should send response");
+
+                let result = pending.await.expect("task should complete");
+                assert!(
+                    matches!(result, Err(Error::InvalidCommand(_))),
+                    "request should fail with invalid command: {result:?}"
+                );
+            });
+    }
+
+    #[test]
+    fn transmission_after_observed_reset_fails_command() {
+        let shared = Shared {
+            version: NegotiatedVersion::new(),
+            sequence: AtomicU8::default(),
+            tickets: AtomicU64::default(),
+            connection: AtomicU64::default(),
+            pending: Mutex::default(),
+        };
+        let (registration, mut receiver) = shared.register(0).expect("sequence should be free");
+
+        shared.reset(2);
+        shared.transmitted(registration.sequence, 1);
+
+        let result = receiver.try_recv().expect("command should be failed");
+        assert!(
+            matches!(result, Err(Error::Io(ref error)) if error.kind() == ErrorKind::ConnectionReset),
+            "command should fail on reset: {result:?}"
+        );
+    }
+}
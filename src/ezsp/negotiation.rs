@@ -0,0 +1,377 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use ezsp::parameters::configuration::{self, version};
+use ezsp::{MIN_NON_LEGACY_VERSION, Parameters, Response};
+use log::{debug, error, info, warn};
+use tokio::sync::watch;
+use tokio::time::timeout;
+
+use super::Client;
+use super::client::WeakClient;
+
+/// Delay before the first retry of a failed version exchange.
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Maximum delay between retries of a failed version exchange.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// EZSP protocol version negotiated with the NCP, shared between EZSP adapters.
+///
+/// Clones share the same version. Pass it to a [`Transmitter`](super::Transmitter) and a
+/// [`Receiver`](super::Receiver) to make them select legacy or extended frame headers by the
+/// current version. [`negotiate`] keeps it up to date.
+#[derive(Clone, Debug, Default)]
+pub struct NegotiatedVersion {
+    inner: Arc<watch::Sender<Option<u8>>>,
+}
+
+impl NegotiatedVersion {
+    /// Create a shared version that has not been negotiated yet.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the negotiated version, if any.
+    #[must_use]
+    pub fn get(&self) -> Option<u8> {
+        *self.inner.borrow()
+    }
+
+    /// Set the negotiated version.
+    pub fn set(&self, version: Option<u8>) {
+        self.inner.send_replace(version);
+    }
+
+    /// Return `true` if the negotiated version requires extended frame headers.
+    #[must_use]
+    pub fn is_extended(&self) -> bool {
+        self.get()
+            .is_some_and(|version| version >= MIN_NON_LEGACY_VERSION.get())
+    }
+
+    /// Wait until a version has been negotiated.
+    pub(crate) async fn negotiated(&self) {
+        // The sender is owned by `self`, so the channel cannot close while waiting.
+        self.subscribe().wait_for(Option::is_some).await.ok();
+    }
+
+    /// Subscribe to changes of the negotiated version.
+    ///
+    /// The version is reset to `None` when the NCP is reset and set again once it has been
+    /// renegotiated.
+    #[must_use]
+    pub fn subscribe(&self) -> watch::Receiver<Option<u8>> {
+        self.inner.subscribe()
+    }
+}
+
+/// Negotiate the EZSP protocol version each time the `ASHv2` connection is established.
+///
+/// Returns a future that the caller must spawn or otherwise poll on their async runtime. Whenever
+/// the actor establishes the connection, i.e. initially and after each NCP reset, the future
+/// clears the client's [`NegotiatedVersion`], sends the EZSP `version` command with
+/// `desired_version` through `client` and stores the protocol version of the NCP's response.
+/// If the NCP reports another version than the desired one, the future repeats the exchange with
+/// the NCP's version, as the EZSP specification requires, and stores that version once the NCP
+/// has accepted it. If the exchange fails, e.g. because the NCP does not respond, it is retried
+/// with exponential backoff until it succeeds or the connection is reestablished.
+/// While the version is unset, a [`Transmitter`](super::Transmitter) sharing it holds back all
+/// frames but the `version` command.
+///
+/// The future does not keep the actor alive. It terminates once the actor has terminated.
+pub fn negotiate(
+    client: &Client,
+    desired_version: u8,
+) -> impl Future<Output = ()> + Send + 'static {
+    run(client.downgrade(), client.connections(), desired_version)
+}
+
+/// Renegotiate the version on every established connection.
+async fn run(client: WeakClient, mut connections: watch::Receiver<u64>, desired_version: u8) {
+    loop {
+        if *connections.borrow_and_update() > 0 {
+            match negotiate_connection(&client, &mut connections, desired_version).await {
+                Outcome::Negotiated => {}
+                Outcome::Reconnected => continue,
+                Outcome::Terminated => break,
+            }
+        }
+
+        if connections.changed().await.is_err() {
+            break;
+        }
+    }
+
+    debug!("EZSP version negotiation terminated.");
+}
+
+/// Negotiate the version of the current connection, retrying failed exchanges with backoff.
+async fn negotiate_connection(
+    client: &WeakClient,
+    connections: &mut watch::Receiver<u64>,
+    desired_version: u8,
+) -> Outcome {
+    client.version().set(None);
+    let mut delay = MIN_RETRY_DELAY;
+
+    loop {
+        let Some(client) = client.upgrade() else {
+            return Outcome::Terminated;
+        };
+
+        if exchange(&client, desired_version).await {
+            return Outcome::Negotiated;
+        }
+
+        drop(client);
+        warn!("Retrying EZSP version negotiation in {delay:?}.");
+
+        match timeout(delay, connections.changed()).await {
+            Err(_) => delay = delay.saturating_mul(2).min(MAX_RETRY_DELAY),
+            Ok(Ok(())) => return Outcome::Reconnected,
+            Ok(Err(_)) => return Outcome::Terminated,
+        }
+    }
+}
+
+/// Outcome of negotiating the version of a connection.
+enum Outcome {
+    Negotiated,
+    Reconnected,
+    Terminated,
+}
+
+/// Perform the `version` exchange and store the negotiated version.
+///
+/// Returns `true` if the version has been negotiated.
+async fn exchange(client: &Client, desired_version: u8) -> bool {
+    let Some(version) = request_version(client, desired_version).await else {
+        return false;
+    };
+
+    if version != desired_version {
+        warn!(
+            "NCP uses EZSP version {version:#04X} instead of desired version \
+             {desired_version:#04X}, switching."
+        );
+
+        match request_version(client, version).await {
+            Some(accepted) if accepted == version => {}
+            Some(accepted) => {
+                error!(
+                    "NCP rejected its own EZSP version {version:#04X}, reporting {accepted:#04X}."
+                );
+                return false;
+            }
+            None => return false,
+        }
+    }
+
+    info!("Negotiated EZSP version {version:#04X}.");
+    client.version().set(Some(version));
+    true
+}
+
+/// Send the `version` command with `version` and return the protocol version of the response.
+async fn request_version(client: &Client, version: u8) -> Option<u8> {
+    match client.request(version::Command::new(version)).await {
+        Ok(Parameters::Response(Response::Configuration(configuration::Response::Version(
+            response,
+        )))) => Some(response.protocol_version()),
+        Ok(parameters) => {
+            error!("Unexpected response to version command: {parameters:?}");
+            None
+        }
+        Err(error) => {
+            warn!("EZSP version negotiation failed: {error}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::time::Duration;
+
+    use ezsp::parameters::utilities::nop;
+    use ezsp::{Command, Commands, Error, Frame, Header, Legacy, Transmit};
+    use tokio::runtime::Builder;
+    use tokio::time::timeout;
+
+    use super::{NegotiatedVersion, negotiate};
+    use crate::code::Code;
+    use crate::ezsp::{Client, Transmitter};
+    use crate::virtual_ncp::{self, VirtualNcp};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+    const VERSION: u8 = 13;
+    const NCP_VERSION: u8 = 8;
+    const HOLD: Duration = Duration::from_millis(100);
+
+    /// Answer the next legacy `version` command and return the requested version.
+    ///
+    /// The response reports `ncp_version`, or the requested version if it is `None`.
+    async fn answer_version(ncp: &mut VirtualNcp, ncp_version: Option<u8>) -> u8 {
+        let command = ncp.receive().await.expect("NCP should receive command");
+        assert_eq!(command.len(), 4, "command should be a version command");
+        assert_eq!(command[2], 0x00, "command should be a version command");
+        let version = ncp_version.unwrap_or(command[3]);
+        ncp.send(
+            [command[0], 0x80, 0x00, version, 0x02, 0x00, 0x74]
+                .into_iter()
+                .collect(),
+        )
+        .await
+        .expect("NCP should send version response");
+        command[3]
+    }
+
+    /// Legacy `nop` frame, which requires an extended header from EZSP version 8 on.
+    fn nop() -> Frame<Commands> {
+        let header = Header::Legacy(Legacy::new(0x42, Command::default().into(), 0x05));
+        Frame::new(header, Commands::from(nop::Command {}))
+    }
+
+    #[test]
+    fn transmitter_holds_frames_until_version_is_negotiated() {
+        Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (host, _host_rx, mut ncp) = virtual_ncp::connect();
+                let version = NegotiatedVersion::new();
+                let mut transmitter = Transmitter::new(host, version.clone());
+                let sent = tokio::spawn(async move { transmitter.transmit(nop()).await });
+
+                assert!(
+                    timeout(HOLD, ncp.receive()).await.is_err(),
+                    "frame should be held back"
+                );
+                version.set(Some(VERSION));
+                assert_eq!(
+                    ncp.receive()
+                        .await
+                        .expect("NCP should receive frame")
+                        .as_slice(),
+                    [0x42, 0x00, 0x01, 0x05, 0x00]
+                );
+                sent.await
+                    .expect("task should complete")
+                    .expect("frame should be sent");
+            });
+    }
+
+    #[test]
+    fn negotiate_sets_version_on_each_connection() {
+        Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (host, host_rx, mut ncp) = virtual_ncp::connect();
+                let version = NegotiatedVersion::new();
+                let mut transmitter = Transmitter::new(host, version.clone());
+                let (client, dispatcher) =
+                    Client::new(transmitter.clone(), host_rx.into(), TIMEOUT);
+                tokio::spawn(dispatcher);
+                tokio::spawn(negotiate(&client, VERSION));
+
+                assert_eq!(answer_version(&mut ncp, None).await, VERSION);
+                version
+                    .subscribe()
+                    .wait_for(|version| *version == Some(VERSION))
+                    .await
+                    .expect("version should be set");
+                assert_eq!(client.negotiated_version(), Some(VERSION));
+
+                // Legacy headers are converted to extended headers.
+                transmitter
+                    .transmit(nop())
+                    .await
+                    .expect("frame should be sent");
+                assert_eq!(
+                    ncp.receive()
+                        .await
+                        .expect("NCP should receive frame")
+                        .as_slice(),
+                    [0x42, 0x00, 0x01, 0x05, 0x00]
+                );
+
+                // The version is renegotiated after the NCP has been reset.
+                ncp.send_error(Code::ExceededMaximumAckTimeoutCount)
+                    .await
+                    .expect("NCP should send ERROR");
+                assert_eq!(answer_version(&mut ncp, None).await, VERSION);
+            });
+    }
+
+    #[test]
+    fn negotiate_retries_unanswered_version_command() {
+        Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (host, host_rx, mut ncp) = virtual_ncp::connect();
+                let version = NegotiatedVersion::new();
+                let (client, dispatcher) = Client::new(
+                    Transmitter::new(host, version.clone()),
+                    host_rx.into(),
+                    HOLD,
+                );
+                tokio::spawn(dispatcher);
+                tokio::spawn(negotiate(&client, VERSION));
+
+                let command = ncp.receive().await.expect("NCP should receive command");
+                assert_eq!(command[2..], [0x00, VERSION], "command should be version");
+
+                // Commands held back for the version fail with their timeout.
+                let result = client.request(nop::Command {}).await;
+                assert!(
+                    matches!(result, Err(Error::Io(ref error)) if error.kind() == ErrorKind::TimedOut),
+                    "request should time out: {result:?}"
+                );
+
+                assert_eq!(answer_version(&mut ncp, None).await, VERSION);
+                version
+                    .subscribe()
+                    .wait_for(|version| *version == Some(VERSION))
+                    .await
+                    .expect("version should be set after retrying");
+            });
+    }
+
+    #[test]
+    fn negotiate_adopts_version_of_ncp() {
+        Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (host, host_rx, mut ncp) = virtual_ncp::connect();
+                let version = NegotiatedVersion::new();
+                let (client, dispatcher) = Client::new(
+                    Transmitter::new(host, version.clone()),
+                    host_rx.into(),
+                    TIMEOUT,
+                );
+                tokio::spawn(dispatcher);
+                tokio::spawn(negotiate(&client, VERSION));
+
+                assert_eq!(answer_version(&mut ncp, Some(NCP_VERSION)).await, VERSION);
+                assert_eq!(
+                    answer_version(&mut ncp, Some(NCP_VERSION)).await,
+                    NCP_VERSION
+                );
+                version
+                    .subscribe()
+                    .wait_for(|version| *version == Some(NCP_VERSION))
+                    .await
+                    .expect("version of the NCP should be set");
+            });
+    }
+}
@@ -1,3 +1,4 @@
+use std::fmt::{Display, Formatter};
 use std::iter::once;
 
 use ezsp::ezsp::{Error as EzspError, Status};
@@ -10,33 +11,67 @@ use le_stream::FromLeStream;
 use log::{debug, trace, warn};
 use tokio::sync::mpsc;
 
+use super::NegotiatedVersion;
 use crate::Payload;
+use crate::hex_slice::HexSlice;
 
 /// Receives `ASHv2` DATA payloads and decodes them as typed EZSP frames.
 pub struct Receiver {
     inner: mpsc::Receiver<Payload>,
+    negotiated_version: Option<NegotiatedVersion>,
 }
 
 impl Receiver {
     /// Creates an EZSP receiver over the `ASHv2` DATA payload channel.
     #[must_use]
     pub const fn new(inner: mpsc::Receiver<Payload>) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            negotiated_version: None,
+        }
+    }
+
+    /// Attach the shared EZSP protocol version negotiated with the NCP.
+    ///
+    /// The receiver then decodes frames with the current version, ignoring the version passed to
+    /// its receive calls.
+    #[must_use]
+    pub fn with_negotiated_version(mut self, version: NegotiatedVersion) -> Self {
+        self.negotiated_version.replace(version);
+        self
+    }
+
+    /// Receives the next payload and decodes it as an EZSP frame.
+    ///
+    /// Unlike [`Receive::receive`], which logs and discards payloads that cannot be decoded, this
+    /// yields decode failures as a [`DecodeError`] carrying the offending payload and, if it could
+    /// be read, the frame header. Callers can use the header's sequence number to fail the
+    /// matching request.
+    ///
+    /// Returns `None` once the payload channel has been closed.
+    pub async fn receive_result(
+        &mut self,
+        negotiated_version: Option<u8>,
+    ) -> Option<Result<Frame<Parameters>, DecodeError>> {
+        let payload = self.inner.recv().await?;
+        let negotiated_version = self
+            .negotiated_version
+            .as_ref()
+            .map_or(negotiated_version, NegotiatedVersion::get);
+        Some(parse_frame(payload, negotiated_version))
     }
 }
 
 impl From<mpsc::Receiver<Payload>> for Receiver {
     fn from(inner: mpsc::Receiver<Payload>) -> Self {
-        Self { inner }
+        Self::new(inner)
     }
 }
 
 impl Receive for Receiver {
     async fn receive(&mut self, negotiated_version: Option<u8>) -> Option<Frame<Parameters>> {
         loop {
-            let frame = self.inner.recv().await?;
-
-            match parse_frame(frame, negotiated_version) {
+            match self.receive_result(negotiated_version).await? {
                 Ok(frame) => return Some(frame),
                 Err(error) => {
                     warn!("{error}");
@@ -46,13 +81,85 @@ impl Receive for Receiver {
     }
 }
 
-fn parse_frame(frame: Payload, negotiated_version: Option<u8>) -> Result<Frame<Parameters>, Error> {
-    trace!("Decoding ASHv2 frame: {frame:#04X?}");
+/// An EZSP frame that could not be decoded.
+#[derive(Debug)]
+pub struct DecodeError {
+    payload: Box<Payload>,
+    header: Option<Header>,
+    error: Error,
+}
+
+impl DecodeError {
+    /// Returns the payload that could not be decoded.
+    #[must_use]
+    pub const fn payload(&self) -> &Payload {
+        &self.payload
+    }
+
+    /// Returns the frame header, if the payload was long enough to contain one.
+    #[must_use]
+    pub const fn header(&self) -> Option<Header> {
+        self.header
+    }
+
+    /// Returns the error that occurred while decoding.
+    #[must_use]
+    pub const fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Returns the error that occurred while decoding, discarding the payload and header.
+    #[must_use]
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to decode EZSP frame ")?;
 
-    let mut stream = frame.into_iter();
-    let header = read_header(&mut stream, negotiated_version).ok_or(Decode::TooFewBytes)?;
+        if let Some(header) = self.header {
+            write!(f, "{header} ")?;
+        }
+
+        write!(f, "{:#04X}: {}", HexSlice::new(&self.payload), self.error)
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+fn parse_frame(
+    payload: Payload,
+    negotiated_version: Option<u8>,
+) -> Result<Frame<Parameters>, DecodeError> {
+    trace!("Decoding ASHv2 frame: {payload:#04X?}");
+
+    let mut stream = payload.clone().into_iter();
+    let Some(header) = read_header(&mut stream, negotiated_version) else {
+        return Err(DecodeError {
+            payload: Box::new(payload),
+            header: None,
+            error: Decode::TooFewBytes.into(),
+        });
+    };
     trace!("Decoded header: {header}");
 
+    parse_parameters(header, stream).map_err(|error| DecodeError {
+        payload: Box::new(payload),
+        header: Some(header),
+        error,
+    })
+}
+
+fn parse_parameters(
+    header: Header,
+    mut stream: <Payload as IntoIterator>::IntoIter,
+) -> Result<Frame<Parameters>, Error> {
     if let LowByte::Response(response) = header.low_byte() {
         if response.is_truncated() {
             return Err(Status::Error(EzspError::Truncated).into());
@@ -100,3 +207,55 @@ where
         Legacy::from_le_stream(stream).map(Header::Legacy)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::iter::once;
+
+    use ezsp::{Error, Header};
+    use tokio::runtime::Builder;
+    use tokio::sync::mpsc::channel;
+
+    use super::Receiver;
+    use crate::Payload;
+
+    /// Legacy `invalidCommand` response to the command with sequence number 3.
+    const INVALID_COMMAND: [u8; 4] = [0x03, 0x80, 0x58, 0x37];
+
+    #[test]
+    fn receive_result_yields_decode_errors() {
+        Builder::new_current_thread()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (tx, rx) = channel(4);
+                let mut receiver = Receiver::new(rx);
+                let invalid_command: Payload = INVALID_COMMAND.into_iter().collect();
+                tx.send(invalid_command.clone())
+                    .await
+                    .expect("payload should be sent");
+                tx.send(once(0x00).collect())
+                    .await
+                    .expect("payload should be sent");
+                drop(tx);
+
+                let error = receiver
+                    .receive_result(None)
+                    .await
+                    .expect("result should be received")
+                    .expect_err("invalid command should not decode");
+                assert_eq!(error.payload(), &invalid_command);
+                assert_eq!(error.header().map(Header::sequence), Some(3));
+                assert!(matches!(error.error(), Error::InvalidCommand(_)));
+
+                let error = receiver
+                    .receive_result(None)
+                    .await
+                    .expect("result should be received")
+                    .expect_err("truncated header should not decode");
+                assert!(error.header().is_none());
+
+                assert!(receiver.receive_result(None).await.is_none());
+            });
+    }
+}
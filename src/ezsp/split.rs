@@ -0,0 +1,145 @@
+use std::iter::once;
+
+use ezsp::LowByte;
+use le_stream::FromLeStream;
+use log::{debug, trace};
+use tokio::sync::mpsc::{self, Sender};
+
+use super::Receiver;
+use crate::Payload;
+
+/// Splits the `ASHv2` DATA payload channel into separate EZSP response and callback receivers.
+///
+/// Returns the receiver of command responses, the receiver of callbacks and the router future
+/// that the caller must spawn or otherwise poll on their async runtime. Each receiver buffers up
+/// to `capacity` payloads. The router waits for room in the receiver a payload is routed to, so
+/// a receiver that is not drained eventually stalls the other one. Payloads for a receiver that
+/// has been dropped are discarded. The router terminates when the payload channel closes or both
+/// receivers have been dropped.
+///
+/// Payloads are routed by the callback type bits of the frame control low byte, which is the
+/// second byte of both legacy and extended EZSP headers. Since the payloads are routed before
+/// decoding, both receivers decode frames with the negotiated version passed to their own
+/// `receive` calls.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn split(
+    inner: mpsc::Receiver<Payload>,
+    capacity: usize,
+) -> (
+    Receiver,
+    Receiver,
+    impl Future<Output = ()> + Send + 'static,
+) {
+    let (responses_tx, responses) = mpsc::channel(capacity);
+    let (callbacks_tx, callbacks) = mpsc::channel(capacity);
+    (
+        Receiver::new(responses),
+        Receiver::new(callbacks),
+        route(inner, responses_tx, callbacks_tx),
+    )
+}
+
+/// Forward payloads to the response or callback receiver.
+async fn route(
+    mut inner: mpsc::Receiver<Payload>,
+    responses: Sender<Payload>,
+    callbacks: Sender<Payload>,
+) {
+    while let Some(payload) = inner.recv().await {
+        let (target, kind) = if is_callback(&payload) {
+            (&callbacks, "callback")
+        } else {
+            (&responses, "response")
+        };
+        trace!("Routing {kind}: {payload:#04X?}");
+
+        if let Err(error) = target.send(payload).await {
+            debug!(
+                "Discarding {kind}, receiver has been dropped: {:#04X?}",
+                error.0
+            );
+
+            if responses.is_closed() && callbacks.is_closed() {
+                break;
+            }
+        }
+    }
+
+    debug!("EZSP callback router terminated.");
+}
+
+/// Returns `true` if the frame control low byte of `payload` marks a callback.
+fn is_callback(payload: &Payload) -> bool {
+    payload
+        .get(1)
+        .and_then(|&low_byte| LowByte::from_le_stream(once(low_byte)))
+        .is_some_and(|low_byte| match low_byte {
+            LowByte::Response(response) => response.callback_type().is_some(),
+            LowByte::Command(_) => false,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use ezsp::{Parameters, Receive};
+    use tokio::runtime::Builder;
+    use tokio::sync::mpsc::channel;
+
+    use super::{is_callback, split};
+    use crate::Payload;
+
+    /// Legacy `version` response.
+    const VERSION_RESPONSE: [u8; 7] = [0x00, 0x80, 0x00, 0x08, 0x02, 0x00, 0x74];
+
+    /// Legacy asynchronous `stackStatusHandler` callback.
+    const STACK_STATUS_CALLBACK: [u8; 4] = [0x01, 0x90, 0x19, 0x90];
+
+    fn payload(bytes: &[u8]) -> Payload {
+        Payload::from_slice(bytes).expect("payload should fit")
+    }
+
+    #[test]
+    fn is_callback_checks_callback_type() {
+        assert!(!is_callback(&payload(&VERSION_RESPONSE)));
+        assert!(is_callback(&payload(&STACK_STATUS_CALLBACK)));
+        // Synchronous callback.
+        assert!(is_callback(&payload(&[0x02, 0x88, 0x19, 0x90])));
+        assert!(!is_callback(&payload(&[0x00])));
+    }
+
+    #[test]
+    fn split_routes_callbacks_separately() {
+        Builder::new_current_thread()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (tx, rx) = channel(4);
+                let (mut responses, mut callbacks, router) = split(rx, 4);
+                tokio::spawn(router);
+
+                tx.send(payload(&STACK_STATUS_CALLBACK))
+                    .await
+                    .expect("callback should be sent");
+                tx.send(payload(&VERSION_RESPONSE))
+                    .await
+                    .expect("response should be sent");
+
+                let (_, parameters) = responses
+                    .receive(None)
+                    .await
+                    .expect("response should be received")
+                    .into();
+                assert!(matches!(parameters, Parameters::Response(_)));
+
+                let (_, parameters) = callbacks
+                    .receive(None)
+                    .await
+                    .expect("callback should be received")
+                    .into();
+                assert!(matches!(parameters, Parameters::Callback(_)));
+            });
+    }
+}
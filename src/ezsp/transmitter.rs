@@ -1,28 +1,155 @@
+use std::io;
+
 use ezsp::ezsp::{Error as EzspError, Status};
-use ezsp::{Commands, Error, Frame, Header, Transmit};
+use ezsp::parameters::configuration;
+use ezsp::{Commands, Error, Extended, Frame, Header, Legacy, Transmit, ValueError};
 use heapless::LenType;
 use le_stream::ToLeStream;
 use log::trace;
 
+use super::NegotiatedVersion;
+use crate::actor::WeakHandle;
 use crate::{Handle, Payload};
 
-/// EZSP transmitter implemented directly by the `ASHv2` actor handle.
-pub type Transmitter = Handle;
+/// EZSP transmitter that adapts frame headers to the negotiated EZSP protocol version.
+///
+/// The transmitter sends frames through the `ASHv2` [`Handle`] and converts the header of each
+/// frame to the legacy or extended format required by its [`NegotiatedVersion`].
+///
+/// Except for the EZSP `version` command, which is sent unchanged, frames are held back until a
+/// version has been negotiated, e.g. by [`negotiate`](super::negotiate) after each NCP reset or
+/// by [`Client::set_negotiated_version`](super::Client::set_negotiated_version).
+#[derive(Clone, Debug)]
+pub struct Transmitter {
+    handle: Handle,
+    version: NegotiatedVersion,
+}
+
+impl Transmitter {
+    /// Create a transmitter sending frames through `handle` with the shared `version`.
+    #[must_use]
+    pub const fn new(handle: Handle, version: NegotiatedVersion) -> Self {
+        Self { handle, version }
+    }
+
+    /// Return the underlying `ASHv2` handle.
+    #[must_use]
+    pub const fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Return the shared negotiated version.
+    #[must_use]
+    pub const fn negotiated_version(&self) -> &NegotiatedVersion {
+        &self.version
+    }
+
+    /// Create a reference to the transmitter that does not keep the actor's message queue open.
+    pub(crate) fn downgrade(&self) -> WeakTransmitter {
+        WeakTransmitter {
+            handle: self.handle.downgrade(),
+            version: self.version.clone(),
+        }
+    }
+
+    /// Send `frame` and return the number of the connection during which it has been sent.
+    pub(crate) async fn send_frame(&self, frame: Frame<Commands>) -> Result<u64, Error> {
+        let (header, parameters) = frame.into();
+
+        if !is_version_command(&parameters) {
+            trace!("Waiting for the EZSP version to be negotiated.");
+            self.version.negotiated().await;
+        }
+
+        let header = convert(header, &self.version)?;
+        let payload = encode(header, parameters)?;
+        Ok(self
+            .handle
+            .send_in_connection(payload)
+            .await
+            .map_err(io::Error::from)?)
+    }
+}
 
 impl Transmit for Transmitter {
+    async fn transmit(&mut self, frame: Frame<Commands>) -> Result<(), Error> {
+        self.send_frame(frame).await.map(drop)
+    }
+}
+
+/// The `ASHv2` actor handle sends frames with their headers unchanged.
+impl Transmit for Handle {
     async fn transmit(&mut self, frame: Frame<Commands>) -> Result<(), Error> {
         let (header, parameters) = frame.into();
-        trace!("Sending EZSP frame: Header: {header:#04X?}, parameters: {parameters:?}");
-        let mut payload = Payload::new();
+        let payload = encode(header, parameters)?;
+        Ok(self.send(payload).await.map_err(io::Error::from)?)
+    }
+}
 
-        match header {
-            Header::Legacy(header) => payload.try_extend(header.to_le_stream())?,
-            Header::Extended(header) => payload.try_extend(header.to_le_stream())?,
-        }
+/// Reference to a [`Transmitter`] that does not keep the actor's message queue open.
+#[derive(Debug)]
+pub struct WeakTransmitter {
+    handle: WeakHandle,
+    version: NegotiatedVersion,
+}
 
-        payload.try_extend(parameters.to_le_stream())?;
-        trace!("Sending EZSP frame (bytes): {payload:#04X?}");
-        Ok(self.send(payload).await?)
+impl WeakTransmitter {
+    /// Return the transmitter, unless every handle to the actor has been dropped.
+    pub fn upgrade(&self) -> Option<Transmitter> {
+        self.handle
+            .upgrade()
+            .map(|handle| Transmitter::new(handle, self.version.clone()))
+    }
+}
+
+/// Return `true` if `parameters` belong to the EZSP `version` command.
+fn is_version_command(parameters: &Commands) -> bool {
+    matches!(
+        parameters,
+        Commands::Configuration(command)
+            if matches!(**command, configuration::Command::Version(_))
+    )
+}
+
+/// Encode an EZSP frame into a payload.
+fn encode(header: Header, parameters: Commands) -> Result<Payload, Error> {
+    trace!("Sending EZSP frame: Header: {header:#04X?}, parameters: {parameters:?}");
+    let mut payload = Payload::new();
+
+    match header {
+        Header::Legacy(header) => payload.try_extend(header.to_le_stream())?,
+        Header::Extended(header) => payload.try_extend(header.to_le_stream())?,
+    }
+
+    payload.try_extend(parameters.to_le_stream())?;
+    trace!("Sending EZSP frame (bytes): {payload:#04X?}");
+    Ok(payload)
+}
+
+/// Convert `header` to the format required by the negotiated `version`, if any.
+fn convert(header: Header, version: &NegotiatedVersion) -> Result<Header, Error> {
+    if version.get().is_none() {
+        return Ok(header);
+    }
+
+    match header {
+        Header::Legacy(legacy) if version.is_extended() => Ok(Header::Extended(Extended::new(
+            legacy.sequence(),
+            legacy.low_byte(),
+            legacy.id().into(),
+        ))),
+        Header::Extended(extended) if !version.is_extended() => {
+            let id = extended
+                .id()
+                .try_into()
+                .map_err(ValueError::InvalidFrameId)?;
+            Ok(Header::Legacy(Legacy::new(
+                extended.sequence(),
+                extended.low_byte(),
+                id,
+            )))
+        }
+        header => Ok(header),
     }
 }
 
@@ -0,0 +1,334 @@
+//! Fault-injecting transport wrapper for link robustness testing.
+//!
+//! This module is available with the `fault-injection` crate feature. [`FaultInjector`] wraps a
+//! transport implementing [`AsyncRead`] and/or [`AsyncWrite`] and injects the [`Faults`]
+//! configured for each direction into the bytes passing through it. All random decisions are
+//! drawn from an RNG seeded by the caller, so a failing scenario can be replayed by reusing its
+//! seed.
+//!
+//! The wrapper splits the byte stream into frames at `FLAG` bytes and applies faults to complete
+//! frames only. Bytes of an unterminated frame are passed on unaltered when the transport reaches
+//! the end of the stream or is shut down.
+//!
+//! ```
+//! use ashv2::fault_injection::{FaultInjector, Faults};
+//! use tokio::io::{duplex, split};
+//! use tokio::sync::mpsc::channel;
+//!
+//! # async fn run() {
+//! let (host, _ncp) = duplex(1024);
+//! let faults = Faults {
+//!     bit_flip: 0.001,
+//!     duplicate_frame: 0.05,
+//!     ..Faults::default()
+//! };
+//! let (reader, writer) = split(FaultInjector::new(host, faults, Faults::default(), 42));
+//! let (response_tx, response_rx) = channel(8);
+//! let (handle, futures) = ashv2::start(reader, writer, response_tx);
+//! # }
+//! ```
+
+use core::pin::Pin;
+use core::task::{Context, Poll, ready};
+use std::io::{self, ErrorKind};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+pub use self::faults::Faults;
+use self::pipeline::Pipeline;
+
+mod faults;
+mod pipeline;
+
+/// Size of the chunks read from the wrapped transport.
+const READ_CHUNK_SIZE: usize = 64;
+
+/// Transport wrapper that injects faults into the bytes read from and written to it.
+#[derive(Debug)]
+pub struct FaultInjector<T> {
+    inner: T,
+    read: Pipeline,
+    write: Pipeline,
+}
+
+impl<T> FaultInjector<T> {
+    /// Wrap `inner`, injecting `read` faults into received and `write` faults into sent bytes.
+    ///
+    /// Both directions use independent RNGs derived from `seed`.
+    #[must_use]
+    pub fn new(inner: T, read: Faults, write: Faults, seed: u64) -> Self {
+        Self {
+            inner,
+            read: Pipeline::new(read, seed),
+            write: Pipeline::new(write, seed.wrapping_add(1)),
+        }
+    }
+
+    /// Return a reference to the wrapped transport.
+    #[must_use]
+    pub const fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Return the wrapped transport.
+    ///
+    /// Bytes still held back by the injector are discarded.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> FaultInjector<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    /// Write all bytes that passed the write pipeline to the wrapped transport.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while let Some(bytes) = ready!(self.write.poll_front(cx)) {
+            let written = ready!(Pin::new(&mut self.inner).poll_write(cx, bytes))?;
+
+            if written == 0 {
+                return Poll::Ready(Err(ErrorKind::WriteZero.into()));
+            }
+
+            self.write.consume(written);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> AsyncRead for FaultInjector<T>
+where
+    T: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(bytes) = ready!(this.read.poll_front(cx)) {
+                let amount = bytes.len().min(buf.remaining());
+                buf.put_slice(&bytes[..amount]);
+                this.read.consume(amount);
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut chunk = [0; READ_CHUNK_SIZE];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk_buf))?;
+
+            if chunk_buf.filled().is_empty() {
+                if this.read.flush_partial() {
+                    continue;
+                }
+
+                return Poll::Ready(Ok(()));
+            }
+
+            this.read.push(chunk_buf.filled());
+        }
+    }
+}
+
+impl<T> AsyncWrite for FaultInjector<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        this.write.push(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.write.flush_partial();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex};
+    use tokio::runtime::Builder;
+    use tokio::time::Instant;
+
+    use super::{FaultInjector, Faults};
+    use crate::frame::RST;
+    use crate::protocol::ControlByte;
+
+    const SEED: u64 = 0x00A5_4812;
+    const BUFFER_SIZE: usize = 4096;
+
+    /// An `ACK` frame followed by a stuffed `DATA` frame.
+    const FRAMES: [u8; 16] = [
+        0x81, 0x60, 0x59, 0x7E, 0x53, 0x00, 0x80, 0x00, 0x02, 0x02, 0x7D, 0x31, 0x30, 0x63, 0x16,
+        0x7E,
+    ];
+
+    fn inject(faults: Faults, seed: u64, bytes: &[u8]) -> Vec<u8> {
+        Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (host, mut ncp) = duplex(BUFFER_SIZE);
+                let mut injector = FaultInjector::new(host, Faults::default(), faults, seed);
+                injector
+                    .write_all(bytes)
+                    .await
+                    .expect("bytes should be written");
+                injector
+                    .shutdown()
+                    .await
+                    .expect("injector should shut down");
+                let mut output = Vec::new();
+                ncp.read_to_end(&mut output)
+                    .await
+                    .expect("bytes should be read");
+                output
+            })
+    }
+
+    #[test]
+    fn passes_bytes_without_faults() {
+        assert_eq!(inject(Faults::default(), SEED, &FRAMES), FRAMES);
+    }
+
+    #[test]
+    fn passes_unterminated_frame_on_shutdown() {
+        assert_eq!(inject(Faults::default(), SEED, &FRAMES[..6]), FRAMES[..6]);
+    }
+
+    #[test]
+    fn same_seed_reproduces_faults() {
+        let faults = Faults {
+            bit_flip: 0.2,
+            drop_byte: 0.1,
+            duplicate_frame: 0.3,
+            substitute: 0.3,
+            ..Faults::default()
+        };
+        let output = inject(faults, SEED, &FRAMES);
+        assert_ne!(output, FRAMES);
+        assert_eq!(inject(faults, SEED, &FRAMES), output);
+    }
+
+    #[test]
+    fn drops_frames() {
+        let faults = Faults {
+            drop_frame: 1.0,
+            ..Faults::default()
+        };
+        assert!(inject(faults, SEED, &FRAMES).is_empty());
+    }
+
+    #[test]
+    fn duplicates_frames() {
+        let faults = Faults {
+            duplicate_frame: 1.0,
+            ..Faults::default()
+        };
+        let (ack, data) = FRAMES.split_at(4);
+        assert_eq!(
+            inject(faults, SEED, &FRAMES),
+            [ack, ack, data, data].concat()
+        );
+    }
+
+    #[test]
+    fn flips_bits() {
+        let faults = Faults {
+            bit_flip: 1.0,
+            ..Faults::default()
+        };
+        let output = inject(faults, SEED, &FRAMES);
+        assert_eq!(output.len(), FRAMES.len());
+        assert!(
+            output
+                .iter()
+                .zip(FRAMES)
+                .all(|(&corrupted, original)| (corrupted ^ original).is_power_of_two())
+        );
+    }
+
+    #[test]
+    fn inserts_substitute_bytes() {
+        let faults = Faults {
+            substitute: 1.0,
+            ..Faults::default()
+        };
+        let mut output = inject(faults, SEED, &FRAMES);
+        assert_eq!(output.len(), FRAMES.len() + 2);
+        output.retain(|&byte| byte != ControlByte::Substitute as u8);
+        assert_eq!(output, FRAMES);
+    }
+
+    #[test]
+    fn injects_spurious_rst() {
+        let faults = Faults {
+            spurious_rst: 1.0,
+            ..Faults::default()
+        };
+        let rst: Vec<u8> = RST.into_iter().chain([ControlByte::Flag as u8]).collect();
+        let (ack, data) = FRAMES.split_at(4);
+        assert_eq!(
+            inject(faults, SEED, &FRAMES),
+            [&rst, ack, &rst, data].concat()
+        );
+    }
+
+    #[test]
+    fn delays_frames() {
+        const DELAY: Duration = Duration::from_millis(500);
+
+        Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (host, ncp) = duplex(BUFFER_SIZE);
+                let faults = Faults {
+                    delay: 1.0,
+                    delay_duration: DELAY,
+                    ..Faults::default()
+                };
+                let mut injector = FaultInjector::new(ncp, faults, Faults::default(), SEED);
+                let mut host = host;
+                host.write_all(&FRAMES)
+                    .await
+                    .expect("bytes should be written");
+                host.shutdown().await.expect("host should shut down");
+
+                let start = Instant::now();
+                let mut output = Vec::new();
+                injector
+                    .read_to_end(&mut output)
+                    .await
+                    .expect("bytes should be read");
+                assert_eq!(output, FRAMES);
+                assert!(start.elapsed() >= DELAY * 2);
+            });
+    }
+}
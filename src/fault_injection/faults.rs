@@ -0,0 +1,53 @@
+//! Configuration of injected faults.
+
+use std::time::Duration;
+
+/// Probabilities of faults injected into one direction of a transport.
+///
+/// All probabilities range from `0.0` (never) to `1.0` (always). Frame-level faults are rolled
+/// once per frame, i.e. per sequence of bytes terminated by a `FLAG` byte. Byte-level faults are
+/// rolled once per byte. The default configuration injects no faults.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Faults {
+    /// Probability of flipping a random bit of a byte.
+    pub bit_flip: f64,
+
+    /// Probability of dropping a byte.
+    pub drop_byte: f64,
+
+    /// Probability of dropping a frame.
+    pub drop_frame: f64,
+
+    /// Probability of sending a frame twice.
+    pub duplicate_frame: f64,
+
+    /// Probability of inserting a `SUBSTITUTE` byte at a random position of a frame.
+    pub substitute: f64,
+
+    /// Probability of sending a spurious `RST` frame before a frame.
+    pub spurious_rst: f64,
+
+    /// Probability of holding back a frame for [`Faults::delay_duration`].
+    pub delay: f64,
+
+    /// The duration for which delayed frames are held back.
+    pub delay_duration: Duration,
+}
+
+impl Faults {
+    /// Returns `true` if the configuration injects no faults.
+    #[must_use]
+    pub fn is_none(&self) -> bool {
+        [
+            self.bit_flip,
+            self.drop_byte,
+            self.drop_frame,
+            self.duplicate_frame,
+            self.substitute,
+            self.spurious_rst,
+            self.delay,
+        ]
+        .iter()
+        .all(|&probability| probability <= 0.0)
+    }
+}
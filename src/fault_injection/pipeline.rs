@@ -0,0 +1,184 @@
+//! Per-direction fault injection pipeline.
+
+use core::pin::Pin;
+use core::task::{Context, Poll, ready};
+use std::collections::VecDeque;
+use std::mem::take;
+use std::time::Duration;
+
+use log::debug;
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+use tokio::time::{Sleep, sleep};
+
+use super::Faults;
+use crate::frame::RST;
+use crate::protocol::ControlByte;
+
+/// A chunk of bytes ready to be passed on.
+#[derive(Debug)]
+struct Chunk {
+    bytes: Vec<u8>,
+    position: usize,
+    delay: Option<Duration>,
+}
+
+impl Chunk {
+    const fn new(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes,
+            position: 0,
+            delay: None,
+        }
+    }
+
+    fn remaining(&self) -> &[u8] {
+        &self.bytes[self.position..]
+    }
+}
+
+/// Splits a byte stream into frames and applies faults to them.
+#[derive(Debug)]
+pub struct Pipeline {
+    faults: Faults,
+    rng: StdRng,
+    frame: Vec<u8>,
+    output: VecDeque<Chunk>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl Pipeline {
+    /// Create a new pipeline applying `faults` with an RNG seeded from `seed`.
+    pub fn new(faults: Faults, seed: u64) -> Self {
+        Self {
+            faults,
+            rng: StdRng::seed_from_u64(seed),
+            frame: Vec::new(),
+            output: VecDeque::new(),
+            sleep: None,
+        }
+    }
+
+    /// Feed bytes into the pipeline.
+    ///
+    /// Faults are applied as soon as a frame is terminated by a `FLAG` byte.
+    pub fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.frame.push(byte);
+
+            if byte == ControlByte::Flag as u8 {
+                let frame = take(&mut self.frame);
+                self.inject(frame);
+            }
+        }
+    }
+
+    /// Pass on the bytes of an unterminated frame without applying faults.
+    ///
+    /// Returns `true` if there were any such bytes.
+    pub fn flush_partial(&mut self) -> bool {
+        if self.frame.is_empty() {
+            return false;
+        }
+
+        let frame = take(&mut self.frame);
+        self.output.push_back(Chunk::new(frame));
+        true
+    }
+
+    /// Poll for the next bytes to pass on.
+    ///
+    /// Returns `Poll::Pending` while the next chunk is being delayed and `None` if there are no
+    /// bytes to pass on.
+    pub fn poll_front(&mut self, cx: &mut Context<'_>) -> Poll<Option<&[u8]>> {
+        let Some(chunk) = self.output.front_mut() else {
+            return Poll::Ready(None);
+        };
+
+        if let Some(delay) = chunk.delay.take() {
+            self.sleep = Some(Box::pin(sleep(delay)));
+        }
+
+        if let Some(sleep) = &mut self.sleep {
+            ready!(sleep.as_mut().poll(cx));
+            self.sleep = None;
+        }
+
+        Poll::Ready(self.output.front().map(Chunk::remaining))
+    }
+
+    /// Mark `amount` bytes returned by [`Self::poll_front`] as passed on.
+    pub fn consume(&mut self, amount: usize) {
+        if let Some(chunk) = self.output.front_mut() {
+            chunk.position += amount;
+
+            if chunk.position >= chunk.bytes.len() {
+                self.output.pop_front();
+            }
+        }
+    }
+
+    fn inject(&mut self, frame: Vec<u8>) {
+        if self.faults.is_none() {
+            self.output.push_back(Chunk::new(frame));
+            return;
+        }
+
+        if self.roll(self.faults.spurious_rst) {
+            debug!("Injecting spurious RST frame.");
+            self.output.push_back(Chunk::new(
+                RST.into_iter().chain([ControlByte::Flag.into()]).collect(),
+            ));
+        }
+
+        if self.roll(self.faults.drop_frame) {
+            debug!("Dropping frame.");
+            return;
+        }
+
+        let mut frame = self.corrupt(frame);
+
+        if self.roll(self.faults.substitute) {
+            // Insert before the trailing FLAG byte, so that the SUBSTITUTE byte corrupts this frame.
+            let index = self.rng.random_range(0..frame.len().max(1));
+            debug!("Inserting SUBSTITUTE byte at index {index}.");
+            frame.insert(index, ControlByte::Substitute.into());
+        }
+
+        let duplicate = self.roll(self.faults.duplicate_frame).then(|| {
+            debug!("Duplicating frame.");
+            Chunk::new(frame.clone())
+        });
+        let mut chunk = Chunk::new(frame);
+
+        if self.roll(self.faults.delay) {
+            debug!("Delaying frame by {:?}.", self.faults.delay_duration);
+            chunk.delay = Some(self.faults.delay_duration);
+        }
+
+        self.output.push_back(chunk);
+        self.output.extend(duplicate);
+    }
+
+    fn corrupt(&mut self, frame: Vec<u8>) -> Vec<u8> {
+        let mut corrupted = Vec::with_capacity(frame.len());
+
+        for mut byte in frame {
+            if self.roll(self.faults.drop_byte) {
+                continue;
+            }
+
+            if self.roll(self.faults.bit_flip) {
+                byte ^= 1 << self.rng.random_range(0..u8::BITS);
+            }
+
+            corrupted.push(byte);
+        }
+
+        corrupted
+    }
+
+    fn roll(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.rng.random_bool(probability.min(1.0))
+    }
+}
@@ -1,11 +1,10 @@
 //! Frame types and their respective headers for the `ASHv2` protocol.
 
 use core::fmt::{Debug, Display, Formatter, LowerHex, UpperHex};
-use std::io::{self, ErrorKind};
-use std::vec::Drain;
 
 pub use self::ack::Ack;
 pub use self::data::Data;
+pub use self::decode_error::DecodeError;
 pub use self::error::Error;
 pub use self::nak::Nak;
 pub use self::rst::{RST, Rst};
@@ -14,6 +13,7 @@ use crate::validate::Validate;
 
 mod ack;
 mod data;
+mod decode_error;
 mod error;
 pub mod headers;
 mod nak;
@@ -22,12 +22,13 @@ mod rst_ack;
 
 /// Available frame types.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[expect(variant_size_differences)]
 pub enum Frame {
     /// `ACK` frame
     Ack(Ack),
 
     /// `DATA` frame
-    Data(Box<Data>),
+    Data(Data),
 
     /// `ERROR` frame
     Error(Error),
@@ -43,7 +44,7 @@ pub enum Frame {
 }
 
 impl Display for Frame {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::Ack(ack) => Display::fmt(ack, f),
             Self::Data(data) => Display::fmt(data, f),
@@ -55,36 +56,27 @@ impl Display for Frame {
     }
 }
 
-impl TryFrom<Drain<'_, u8>> for Frame {
-    type Error = io::Error;
+impl TryFrom<&[u8]> for Frame {
+    type Error = DecodeError;
 
-    fn try_from(buffer: Drain<'_, u8>) -> io::Result<Self> {
-        let mut buffer = buffer.peekable();
-        match *buffer
-            .peek()
-            .ok_or_else(|| io::Error::new(ErrorKind::UnexpectedEof, "Missing frame header."))?
-        {
+    fn try_from(buffer: &[u8]) -> Result<Self, DecodeError> {
+        match *buffer.first().ok_or(DecodeError::MissingHeader)? {
             Rst::HEADER => Rst::try_from(buffer).map(Self::Rst),
             RstAck::HEADER => RstAck::try_from(buffer).map(Self::RstAck),
             Error::HEADER => Error::try_from(buffer).map(Self::Error),
-            header if header & 0x80 == 0x00 => {
-                Data::try_from(buffer).map(|data| Self::Data(data.into()))
-            }
+            header if header & 0x80 == 0x00 => Data::try_from(buffer).map(Self::Data),
             header if header & 0x60 == 0x00 => Ack::try_from(buffer).map(Self::Ack),
             header if header & 0x60 == 0x20 => Nak::try_from(buffer).map(Self::Nak),
-            header => Err(io::Error::new(
-                ErrorKind::InvalidData,
-                format!("Unknown frame header: {header:#04X}"),
-            )),
+            header => Err(DecodeError::UnknownHeader(header)),
         }
     }
 }
 
 impl LowerHex for Frame {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::Ack(ack) => LowerHex::fmt(ack, f),
-            Self::Data(data) => LowerHex::fmt(data.as_ref(), f),
+            Self::Data(data) => LowerHex::fmt(data, f),
             Self::Error(error) => LowerHex::fmt(error, f),
             Self::Nak(nak) => LowerHex::fmt(nak, f),
             Self::Rst(rst) => LowerHex::fmt(rst, f),
@@ -94,10 +86,10 @@ impl LowerHex for Frame {
 }
 
 impl UpperHex for Frame {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::Ack(ack) => UpperHex::fmt(ack, f),
-            Self::Data(data) => UpperHex::fmt(data.as_ref(), f),
+            Self::Data(data) => UpperHex::fmt(data, f),
             Self::Error(error) => UpperHex::fmt(error, f),
             Self::Nak(nak) => UpperHex::fmt(nak, f),
             Self::Rst(rst) => UpperHex::fmt(rst, f),
@@ -133,22 +125,23 @@ impl Validate for Frame {
 #[cfg(test)]
 #[expect(clippy::unwrap_used)]
 mod tests {
-    use super::{Frame, Rst};
+    use super::{DecodeError, Frame, Rst};
+    use crate::MAX_PAYLOAD_SIZE;
     use crate::code::Code;
     use crate::validate::Validate;
 
     #[test]
     fn test_rst_try_from_bytes_slice() {
-        let mut buffer = vec![0xC0, 0x38, 0xBC, 0x7E];
-        let packet = Frame::try_from(buffer.drain(..buffer.len().saturating_sub(1))).unwrap();
+        let buffer = [0xC0, 0x38, 0xBC, 0x7E];
+        let packet = Frame::try_from(&buffer[..buffer.len().saturating_sub(1)]).unwrap();
         assert_eq!(packet, Frame::Rst(Rst::new()));
     }
 
     #[test]
     fn test_rstack_try_from_bytes_slice() {
-        let mut buffer = vec![0xC1, 0x02, 0x02, 0x9B, 0x7B, 0x7E];
+        let buffer = [0xC1, 0x02, 0x02, 0x9B, 0x7B, 0x7E];
 
-        match Frame::try_from(buffer.drain(..buffer.len().saturating_sub(1))).unwrap() {
+        match Frame::try_from(&buffer[..buffer.len().saturating_sub(1)]).unwrap() {
             Frame::RstAck(rst_ack) => {
                 assert!(rst_ack.is_ash_v2());
                 assert_eq!(rst_ack.version(), 2);
@@ -161,9 +154,9 @@ mod tests {
 
     #[test]
     fn test_error_try_from_bytes_slice() {
-        let mut buffer = vec![0xC2, 0x02, 0x52, 0x98, 0xDE, 0x7E];
+        let buffer = [0xC2, 0x02, 0x52, 0x98, 0xDE, 0x7E];
 
-        match Frame::try_from(buffer.drain(..buffer.len().saturating_sub(1))).unwrap() {
+        match Frame::try_from(&buffer[..buffer.len().saturating_sub(1)]).unwrap() {
             Frame::Error(error) => {
                 assert_eq!(error.version(), 2);
                 assert_eq!(error.code(), Err(0x52));
@@ -175,11 +168,11 @@ mod tests {
 
     #[test]
     fn test_data_try_from_bytes_slice() {
-        let buffer = vec![
+        let buffer = [
             0x53, 0x00, 0x80, 0x00, 0x02, 0x02, 0x11, 0x30, 0x63, 0x16, 0x7E,
         ];
 
-        match Frame::try_from(buffer.clone().drain(..buffer.len().saturating_sub(1))).unwrap() {
+        match Frame::try_from(&buffer[..buffer.len().saturating_sub(1)]).unwrap() {
             Frame::Data(data) => {
                 assert_eq!(data.crc(), 0x6316);
                 let data = data.validate().unwrap();
@@ -191,9 +184,9 @@ mod tests {
 
     #[test]
     fn test_ack_try_from_bytes_slice() {
-        let mut buffer = vec![0x81, 0x60, 0x59, 0x7E];
+        let buffer = [0x81, 0x60, 0x59, 0x7E];
 
-        match Frame::try_from(buffer.drain(..buffer.len().saturating_sub(1))).unwrap() {
+        match Frame::try_from(&buffer[..buffer.len().saturating_sub(1)]).unwrap() {
             Frame::Ack(ack) => {
                 assert!(!ack.not_ready());
                 assert_eq!(ack.ack_num(), 1);
@@ -203,9 +196,9 @@ mod tests {
             packet => panic!("Expected Ack, got {packet:?}"),
         }
 
-        let mut buffer = vec![0x8E, 0x91, 0xB6, 0x7E];
+        let buffer = [0x8E, 0x91, 0xB6, 0x7E];
 
-        match Frame::try_from(buffer.drain(..buffer.len().saturating_sub(1))).unwrap() {
+        match Frame::try_from(&buffer[..buffer.len().saturating_sub(1)]).unwrap() {
             Frame::Ack(ack) => {
                 assert!(ack.not_ready());
                 assert_eq!(ack.ack_num(), 0x06);
@@ -218,9 +211,9 @@ mod tests {
 
     #[test]
     fn test_nak_try_from_bytes_slice() {
-        let mut buffer = vec![0xA6, 0x34, 0xDC, 0x7E];
+        let buffer = [0xA6, 0x34, 0xDC, 0x7E];
 
-        match Frame::try_from(buffer.drain(..buffer.len().saturating_sub(1))).unwrap() {
+        match Frame::try_from(&buffer[..buffer.len().saturating_sub(1)]).unwrap() {
             Frame::Nak(nak) => {
                 assert!(!nak.not_ready());
                 assert_eq!(nak.ack_num(), 0x06);
@@ -230,9 +223,9 @@ mod tests {
             packet => panic!("Expected Nak, got {packet:?}"),
         }
 
-        let mut buffer = vec![0xAD, 0x85, 0xB7, 0x7E];
+        let buffer = [0xAD, 0x85, 0xB7, 0x7E];
 
-        match Frame::try_from(buffer.drain(..buffer.len().saturating_sub(1))).unwrap() {
+        match Frame::try_from(&buffer[..buffer.len().saturating_sub(1)]).unwrap() {
             Frame::Nak(nak) => {
                 assert!(nak.not_ready());
                 assert_eq!(nak.ack_num(), 0x05);
@@ -242,4 +235,31 @@ mod tests {
             packet => panic!("Expected Nak, got {packet:?}"),
         }
     }
+
+    #[test]
+    fn test_empty_try_from_bytes_slice() {
+        assert_eq!(
+            Frame::try_from([].as_slice()),
+            Err(DecodeError::MissingHeader)
+        );
+    }
+
+    #[test]
+    fn test_unknown_header_try_from_bytes_slice() {
+        let buffer = [0xC3, 0x00, 0x00];
+        assert_eq!(
+            Frame::try_from(buffer.as_slice()),
+            Err(DecodeError::UnknownHeader(0xC3))
+        );
+    }
+
+    #[test]
+    fn test_oversized_data_try_from_bytes_slice() {
+        let mut buffer = vec![0x00; MAX_PAYLOAD_SIZE + 4];
+        buffer[0] = 0x25;
+        assert_eq!(
+            Frame::try_from(buffer.as_slice()),
+            Err(DecodeError::PayloadTooLong(MAX_PAYLOAD_SIZE + 1))
+        );
+    }
 }
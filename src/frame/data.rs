@@ -1,10 +1,9 @@
 //! Data frame implementation.
 
 use core::fmt::{Display, Formatter, LowerHex, UpperHex};
-use std::io::{self, Error, ErrorKind};
-use std::iter::{Chain, Copied, Once, Peekable, once};
-use std::vec::Drain;
+use core::iter::{Chain, Copied, Once, once};
 
+use super::DecodeError;
 use super::headers::data::Header;
 use crate::MAX_PAYLOAD_SIZE;
 use crate::hex_slice::HexSlice;
@@ -68,6 +67,12 @@ impl Data {
         self.crc = self.calculate_crc();
     }
 
+    /// Returns the length of the payload.
+    #[must_use]
+    pub fn payload_len(&self) -> usize {
+        self.payload.len()
+    }
+
     /// Consumes the `Data` frame and returns its payload.
     #[must_use]
     pub fn into_payload(self) -> Payload {
@@ -81,7 +86,7 @@ impl Data {
 }
 
 impl Display for Data {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "DATA({}, {}, {})",
@@ -117,7 +122,7 @@ impl<'data> IntoIterator for &'data Data {
 }
 
 impl UpperHex for Data {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "Data {{ header: ")?;
         UpperHex::fmt(&self.header.bits(), f)?;
         write!(f, ", payload: ")?;
@@ -130,7 +135,7 @@ impl UpperHex for Data {
 
 /// Display unmasked payload for debugging.
 impl LowerHex for Data {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "Data {{ header: ")?;
         LowerHex::fmt(&self.header.bits(), f)?;
         write!(f, ", payload: ")?;
@@ -141,31 +146,22 @@ impl LowerHex for Data {
     }
 }
 
-impl TryFrom<Peekable<Drain<'_, u8>>> for Data {
-    type Error = Error;
-
-    fn try_from(mut buffer: Peekable<Drain<'_, u8>>) -> io::Result<Self> {
-        let header = buffer
-            .next()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
-        let crc1 = buffer
-            .next_back()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
-        let crc0 = buffer
-            .next_back()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
-        let payload: Payload = buffer.collect();
+impl TryFrom<&[u8]> for Data {
+    type Error = DecodeError;
+
+    fn try_from(buffer: &[u8]) -> Result<Self, DecodeError> {
+        let [header, ref payload @ .., crc0, crc1] = *buffer else {
+            return Err(DecodeError::UnexpectedEof);
+        };
 
         if payload.len() < Self::MIN_PAYLOAD_SIZE {
-            return Err(Error::new(
-                ErrorKind::UnexpectedEof,
-                "Too few bytes for payload for DATA.",
-            ));
+            return Err(DecodeError::PayloadTooShort(payload.len()));
         }
 
         Ok(Self {
             header: Header::from_bits_retain(header),
-            payload,
+            payload: Payload::from_slice(payload)
+                .map_err(|_| DecodeError::PayloadTooLong(payload.len()))?,
             crc: u16::from_be_bytes([crc0, crc1]),
         })
     }
@@ -328,16 +324,16 @@ mod tests {
     #[test]
     fn test_from_buffer() {
         // EZSP "version" command: 00 00 00 02
-        let mut buffer: Vec<u8> = vec![0x25, 0x00, 0x00, 0x00, 0x02, 0x1A, 0xAD];
+        let buffer = [0x25, 0x00, 0x00, 0x00, 0x02, 0x1A, 0xAD];
         let data = Data {
             header: Header::from_bits_retain(0x25),
             payload: [0x00, 0x00, 0x00, 0x02].as_slice().try_into().unwrap(),
             crc: 0x1AAD,
         };
-        assert_eq!(Data::try_from(buffer.drain(..).peekable()).unwrap(), data);
+        assert_eq!(Data::try_from(buffer.as_slice()).unwrap(), data);
 
         // EZSP "version" response: 00 80 00 02 02 11 30
-        let mut buffer: Vec<u8> = vec![0x53, 0x00, 0x80, 0x00, 0x02, 0x02, 0x11, 0x30, 0x63, 0x16];
+        let buffer = [0x53, 0x00, 0x80, 0x00, 0x02, 0x02, 0x11, 0x30, 0x63, 0x16];
         let data = Data {
             header: Header::from_bits_retain(0x53),
             payload: [0x00, 0x80, 0x00, 0x02, 0x02, 0x11, 0x30]
@@ -346,7 +342,7 @@ mod tests {
                 .unwrap(),
             crc: 0x6316,
         };
-        assert_eq!(Data::try_from(buffer.drain(..).peekable()).unwrap(), data);
+        assert_eq!(Data::try_from(buffer.as_slice()).unwrap(), data);
     }
 
     #[test]
@@ -0,0 +1,58 @@
+//! Errors that can occur when decoding `ASHv2` frames.
+
+use core::fmt::{Display, Formatter};
+
+/// Errors that can occur when decoding raw bytes into `ASHv2` frames.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DecodeError {
+    /// The buffer does not contain a frame header.
+    MissingHeader,
+
+    /// The buffer ended before all fields of the frame could be read.
+    UnexpectedEof,
+
+    /// The `DATA` frame's payload is shorter than the minimum payload size.
+    PayloadTooShort(usize),
+
+    /// The `DATA` frame's payload exceeds the maximum payload size.
+    PayloadTooLong(usize),
+
+    /// The frame header does not denote any known frame type.
+    UnknownHeader(u8),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::MissingHeader => write!(f, "Missing frame header."),
+            Self::UnexpectedEof => write!(f, "Unexpected end of frame."),
+            Self::PayloadTooShort(size) => {
+                write!(f, "Too few bytes for payload for DATA: {size}")
+            }
+            Self::PayloadTooLong(size) => {
+                write!(f, "Too many bytes for payload for DATA: {size}")
+            }
+            Self::UnknownHeader(header) => write!(f, "Unknown frame header: {header:#04X}"),
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+#[cfg(feature = "std")]
+impl From<DecodeError> for std::io::Error {
+    fn from(error: DecodeError) -> Self {
+        use std::io::ErrorKind;
+
+        let kind = match error {
+            DecodeError::MissingHeader
+            | DecodeError::UnexpectedEof
+            | DecodeError::PayloadTooShort(_) => ErrorKind::UnexpectedEof,
+            DecodeError::PayloadTooLong(_) | DecodeError::UnknownHeader(_) => {
+                ErrorKind::InvalidData
+            }
+        };
+
+        Self::new(kind, error)
+    }
+}
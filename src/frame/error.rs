@@ -1,13 +1,11 @@
 //! Error frame implementation.
 
 use core::fmt::{Display, Formatter, LowerHex, UpperHex};
-use std::io;
-use std::io::ErrorKind;
-use std::iter::{Chain, Peekable};
-use std::vec::Drain;
+use core::iter::Chain;
 
 use num_traits::FromPrimitive;
 
+use super::DecodeError;
 use crate::code::Code;
 use crate::hex_slice::HexSlice;
 use crate::validate::{CRC, Validate};
@@ -25,6 +23,19 @@ impl Error {
     /// Constant header value for `ERROR` frames.
     pub const HEADER: u8 = 0xC2;
 
+    /// Creates a new `ASHv2` `ERROR` frame with the given error code.
+    #[must_use]
+    pub const fn new(code: Code) -> Self {
+        let code = code as u8;
+
+        Self {
+            header: Self::HEADER,
+            version: crate::VERSION,
+            code,
+            crc: CRC.checksum(&[Self::HEADER, crate::VERSION, code]),
+        }
+    }
+
     /// Returns the protocol version.
     ///
     /// This is statically set to `0x02` (2) for `ASHv2`.
@@ -50,7 +61,7 @@ impl Error {
 }
 
 impl Display for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "ERROR({:#04X}, {:#04X})", self.version, self.code)
     }
 }
@@ -76,25 +87,13 @@ impl IntoIterator for Error {
     }
 }
 
-impl TryFrom<Peekable<Drain<'_, u8>>> for Error {
-    type Error = io::Error;
-
-    fn try_from(mut buffer: Peekable<Drain<'_, u8>>) -> io::Result<Self> {
-        let header = buffer
-            .next()
-            .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
-        let version = buffer
-            .next()
-            .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
-        let code = buffer
-            .next()
-            .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
-        let crc0 = buffer
-            .next()
-            .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
-        let crc1 = buffer
-            .next()
-            .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
+impl TryFrom<&[u8]> for Error {
+    type Error = DecodeError;
+
+    fn try_from(buffer: &[u8]) -> Result<Self, DecodeError> {
+        let [header, version, code, crc0, crc1, ..] = *buffer else {
+            return Err(DecodeError::UnexpectedEof);
+        };
 
         Ok(Self {
             header,
@@ -106,7 +105,7 @@ impl TryFrom<Peekable<Drain<'_, u8>>> for Error {
 }
 
 impl UpperHex for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "Error {{ header: ")?;
         UpperHex::fmt(&self.header, f)?;
         write!(f, ", version: ")?;
@@ -120,7 +119,7 @@ impl UpperHex for Error {
 }
 
 impl LowerHex for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "Error {{ header: ")?;
         LowerHex::fmt(&self.header, f)?;
         write!(f, ", version: ")?;
@@ -146,6 +145,11 @@ mod tests {
         crc: 0xA8BD,
     };
 
+    #[test]
+    fn test_new() {
+        assert_eq!(Error::new(Code::ExceededMaximumAckTimeoutCount), ERROR);
+    }
+
     #[test]
     fn test_version() {
         assert_eq!(ERROR.version(), 2);
@@ -178,10 +182,9 @@ mod tests {
 
     #[test]
     fn test_from_buffer() {
-        let mut buffer: Vec<u8> = vec![0xC2, 0x02, 0x51, 0xA8, 0xBD];
+        let buffer = [0xC2, 0x02, 0x51, 0xA8, 0xBD];
         assert_eq!(
-            Error::try_from(buffer.drain(..).peekable())
-                .expect("Reference frame should be a valid ERROR."),
+            Error::try_from(buffer.as_slice()).expect("Reference frame should be a valid ERROR."),
             ERROR
         );
     }
@@ -1,10 +1,9 @@
 //! Acknowledgement (`ACK`) frame implementation.
 
 use core::fmt::{Display, Formatter, LowerHex, UpperHex};
-use std::io::{self, Error, ErrorKind};
-use std::iter::{Chain, Once, Peekable, once};
-use std::vec::Drain;
+use core::iter::{Chain, Once, once};
 
+use super::DecodeError;
 use super::headers::nak::Header;
 use crate::hex_slice::HexSlice;
 use crate::validate::{CRC, Validate};
@@ -42,7 +41,7 @@ impl Nak {
 }
 
 impl Display for Nak {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "NAK({}){}",
@@ -71,19 +70,13 @@ impl IntoIterator for Nak {
     }
 }
 
-impl TryFrom<Peekable<Drain<'_, u8>>> for Nak {
-    type Error = Error;
+impl TryFrom<&[u8]> for Nak {
+    type Error = DecodeError;
 
-    fn try_from(mut buffer: Peekable<Drain<'_, u8>>) -> io::Result<Self> {
-        let header = buffer
-            .next()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
-        let crc0 = buffer
-            .next()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
-        let crc1 = buffer
-            .next()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
+    fn try_from(buffer: &[u8]) -> Result<Self, DecodeError> {
+        let [header, crc0, crc1, ..] = *buffer else {
+            return Err(DecodeError::UnexpectedEof);
+        };
 
         Ok(Self {
             header: Header::from_bits_retain(header),
@@ -93,7 +86,7 @@ impl TryFrom<Peekable<Drain<'_, u8>>> for Nak {
 }
 
 impl UpperHex for Nak {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "Nak {{ header: ")?;
         UpperHex::fmt(&self.header.bits(), f)?;
         write!(f, ", crc: ")?;
@@ -103,7 +96,7 @@ impl UpperHex for Nak {
 }
 
 impl LowerHex for Nak {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "Nak {{ header: ")?;
         LowerHex::fmt(&self.header.bits(), f)?;
         write!(f, ", crc: ")?;
@@ -164,16 +157,14 @@ mod tests {
 
     #[test]
     fn test_from_buffer() {
-        let mut buffer1 = vec![0xA6, 0x34, 0xDC];
+        let buffer1 = [0xA6, 0x34, 0xDC];
         assert_eq!(
-            Nak::try_from(buffer1.drain(..).peekable())
-                .expect("Reference frame should be a valid NAK"),
+            Nak::try_from(buffer1.as_slice()).expect("Reference frame should be a valid NAK"),
             NAK1
         );
-        let mut buffer2 = vec![0xAD, 0x85, 0xB7];
+        let buffer2 = [0xAD, 0x85, 0xB7];
         assert_eq!(
-            Nak::try_from(buffer2.drain(..).peekable())
-                .expect("Reference frame should be a valid NAK"),
+            Nak::try_from(buffer2.as_slice()).expect("Reference frame should be a valid NAK"),
             NAK2
         );
     }
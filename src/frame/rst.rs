@@ -1,13 +1,13 @@
 //! Reset (`RST`) frame implementation.
 
 use core::fmt::{Display, Formatter, LowerHex, UpperHex};
-use std::io::{self, Error, ErrorKind};
-use std::iter::{Chain, Once, Peekable, once};
-use std::vec::Drain;
+use core::iter::{Chain, Once, once};
 
+use super::DecodeError;
 use crate::hex_slice::HexSlice;
 use crate::validate::{CRC, Validate};
 
+/// A ready-to-send `RST` frame.
 pub const RST: Rst = Rst::new();
 
 /// Requests the NCP to perform a software reset (valid even if the NCP is in the FAILED state).
@@ -40,7 +40,7 @@ impl Default for Rst {
 }
 
 impl Display for Rst {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "RST()")
     }
 }
@@ -64,19 +64,13 @@ impl IntoIterator for Rst {
     }
 }
 
-impl TryFrom<Peekable<Drain<'_, u8>>> for Rst {
-    type Error = Error;
+impl TryFrom<&[u8]> for Rst {
+    type Error = DecodeError;
 
-    fn try_from(mut buffer: Peekable<Drain<'_, u8>>) -> io::Result<Self> {
-        let header = buffer
-            .next()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
-        let crc0 = buffer
-            .next()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
-        let crc1 = buffer
-            .next()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
+    fn try_from(buffer: &[u8]) -> Result<Self, DecodeError> {
+        let [header, crc0, crc1, ..] = *buffer else {
+            return Err(DecodeError::UnexpectedEof);
+        };
 
         Ok(Self {
             header,
@@ -86,7 +80,7 @@ impl TryFrom<Peekable<Drain<'_, u8>>> for Rst {
 }
 
 impl UpperHex for Rst {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "Rst {{ header: ")?;
         UpperHex::fmt(&self.header, f)?;
         write!(f, ", crc: ")?;
@@ -96,7 +90,7 @@ impl UpperHex for Rst {
 }
 
 impl LowerHex for Rst {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "Rst {{ header: ")?;
         LowerHex::fmt(&self.header, f)?;
         write!(f, ", crc: ")?;
@@ -137,10 +131,9 @@ mod tests {
 
     #[test]
     fn test_from_buffer() {
-        let mut buffer: Vec<u8> = vec![0xC0, 0x38, 0xBC];
+        let buffer = [0xC0, 0x38, 0xBC];
         assert_eq!(
-            Rst::try_from(buffer.drain(..).peekable())
-                .expect("Reference frame should be a valid RST."),
+            Rst::try_from(buffer.as_slice()).expect("Reference frame should be a valid RST."),
             RST
         );
     }
@@ -1,12 +1,11 @@
 //! Reset acknowledgment (`RST_ACK`) frame implementation.
 
 use core::fmt::{Display, Formatter, LowerHex, UpperHex};
-use std::io::{self, Error, ErrorKind};
-use std::iter::{Chain, Peekable};
-use std::vec::Drain;
+use core::iter::Chain;
 
 use num_traits::FromPrimitive;
 
+use super::DecodeError;
 use crate::VERSION;
 use crate::code::Code;
 use crate::hex_slice::HexSlice;
@@ -25,6 +24,19 @@ impl RstAck {
     /// Constant header value for `RST_ACK` frames.
     pub const HEADER: u8 = 0xC1;
 
+    /// Creates a new `ASHv2` `RST_ACK` frame with the given reset code.
+    #[must_use]
+    pub const fn new(code: Code) -> Self {
+        let reset_code = code as u8;
+
+        Self {
+            header: Self::HEADER,
+            version: VERSION,
+            reset_code,
+            crc: CRC.checksum(&[Self::HEADER, VERSION, reset_code]),
+        }
+    }
+
     /// Returns the protocol version.
     ///
     /// This is statically set to `0x02` (2) for `ASHv2`.
@@ -50,7 +62,7 @@ impl RstAck {
 }
 
 impl Display for RstAck {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self.code() {
             Ok(code) => write!(f, "RSTACK({:#04X}, {code})", self.version),
             Err(code) => write!(f, "RSTACK({:#04X}, {code:#04X})", self.version),
@@ -79,25 +91,13 @@ impl IntoIterator for RstAck {
     }
 }
 
-impl TryFrom<Peekable<Drain<'_, u8>>> for RstAck {
-    type Error = Error;
-
-    fn try_from(mut buffer: Peekable<Drain<'_, u8>>) -> io::Result<Self> {
-        let header = buffer
-            .next()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
-        let version = buffer
-            .next()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
-        let reset_code = buffer
-            .next()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
-        let crc0 = buffer
-            .next()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
-        let crc1 = buffer
-            .next()
-            .ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
+impl TryFrom<&[u8]> for RstAck {
+    type Error = DecodeError;
+
+    fn try_from(buffer: &[u8]) -> Result<Self, DecodeError> {
+        let [header, version, reset_code, crc0, crc1, ..] = *buffer else {
+            return Err(DecodeError::UnexpectedEof);
+        };
 
         Ok(Self {
             header,
@@ -109,7 +109,7 @@ impl TryFrom<Peekable<Drain<'_, u8>>> for RstAck {
 }
 
 impl UpperHex for RstAck {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "RstAck {{ header: ")?;
         UpperHex::fmt(&self.header, f)?;
         write!(f, ", version: ")?;
@@ -123,7 +123,7 @@ impl UpperHex for RstAck {
 }
 
 impl LowerHex for RstAck {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "RstAck {{ header: ")?;
         LowerHex::fmt(&self.header, f)?;
         write!(f, ", version: ")?;
@@ -149,6 +149,11 @@ mod tests {
         crc: 0x9B7B,
     };
 
+    #[test]
+    fn test_new() {
+        assert_eq!(RstAck::new(Code::PowerOn), RST_ACK);
+    }
+
     #[test]
     fn test_version() {
         assert_eq!(RST_ACK.version(), 0x02);
@@ -181,10 +186,9 @@ mod tests {
 
     #[test]
     fn test_from_buffer() {
-        let mut buffer: Vec<u8> = vec![0xC1, 0x02, 0x02, 0x9B, 0x7B];
+        let buffer = [0xC1, 0x02, 0x02, 0x9B, 0x7B];
         assert_eq!(
-            RstAck::try_from(buffer.drain(..).peekable())
-                .expect("Reference frame should be a valid RSTACK"),
+            RstAck::try_from(buffer.as_slice()).expect("Reference frame should be a valid RSTACK"),
             RST_ACK
         );
     }
@@ -0,0 +1,281 @@
+//! TCP gateway that shares an `ASHv2` connection with remote clients.
+//!
+//! This module is available with the `gateway` crate feature. A [`Gateway`] owns the [`Handle`]
+//! and the response channel of an actor created with [`crate::start`] and terminates the `ASHv2`
+//! protocol locally. Remote clients exchange unmasked `DATA` payloads, i.e. plain EZSP frames,
+//! over TCP and never see `ASHv2` framing, acknowledgements or retransmissions.
+//!
+//! # Framing
+//!
+//! Every payload is prefixed with its length as a big-endian `u16`:
+//!
+//! | Offset | Size       | Content                  |
+//! |--------|------------|--------------------------|
+//! | 0      | 2          | Payload length `n`       |
+//! | 2      | `n`        | Unmasked `DATA` payload  |
+//!
+//! Payloads longer than [`MAX_PAYLOAD_SIZE`] are rejected and the
+//! client is disconnected.
+//!
+//! # Clients
+//!
+//! The gateway serves one client at a time, since EZSP sequence numbers and callbacks are not
+//! meaningful to more than one host. Further clients are accepted once the current one
+//! disconnects. Payloads received from the NCP while no client is connected are discarded. A
+//! client is disconnected if one of its payloads cannot be transmitted to the NCP.
+//!
+//! # Backpressure
+//!
+//! A client payload is only read after the previous one has been transmitted to the NCP, and a
+//! payload from the NCP is only taken from the response channel after the previous one has been
+//! written to the client. A slow client thus throttles the actor instead of growing a buffer.
+//!
+//! ```no_run
+//! use ashv2::gateway::Gateway;
+//! use tokio::net::TcpListener;
+//! use tokio::sync::mpsc::channel;
+//!
+//! # async fn run(reader: tokio::io::DuplexStream, writer: tokio::io::DuplexStream) -> std::io::Result<()> {
+//! let (response_tx, response_rx) = channel(64);
+//! let (handle, futures) = ashv2::start(reader, writer, response_tx);
+//! tokio::spawn(futures.transmitter);
+//! tokio::spawn(futures.receiver);
+//!
+//! let listener = TcpListener::bind("0.0.0.0:6638").await?;
+//! Gateway::new(listener, handle, response_rx).run().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::poll_fn;
+use std::io::{self, ErrorKind};
+use std::pin::pin;
+use std::task::Poll;
+
+use bytes::{Bytes, BytesMut};
+use log::{debug, info, warn};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::Receiver;
+use tokio_stream::StreamExt;
+use tokio_util::codec::{Encoder, FramedRead, LengthDelimitedCodec};
+
+use crate::error::Error;
+use crate::types::Payload;
+use crate::{Handle, MAX_PAYLOAD_SIZE};
+
+/// Gateway that exposes the payloads of an `ASHv2` connection to TCP clients.
+#[derive(Debug)]
+pub struct Gateway {
+    listener: TcpListener,
+    handle: Handle,
+    payloads: Receiver<Payload>,
+}
+
+impl Gateway {
+    /// Create a gateway that accepts clients on `listener`.
+    ///
+    /// The `handle` and `payloads` are the actor's handle and the receiving end of its response
+    /// channel.
+    #[must_use]
+    pub const fn new(listener: TcpListener, handle: Handle, payloads: Receiver<Payload>) -> Self {
+        Self {
+            listener,
+            handle,
+            payloads,
+        }
+    }
+
+    /// Accept and serve clients until the actor terminates.
+    ///
+    /// Errors of individual client connections are logged and do not stop the gateway.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if accepting a client fails.
+    pub async fn run(mut self) -> io::Result<()> {
+        while let Some((client, addr)) = self.accept().await? {
+            info!("Client {addr} connected.");
+            client.set_nodelay(true)?;
+
+            match self.serve(client).await {
+                Ok(()) => info!("Client {addr} disconnected."),
+                Err(error) => warn!("Client {addr} disconnected: {error}"),
+            }
+        }
+
+        info!("Actor terminated, gateway exiting.");
+        Ok(())
+    }
+
+    /// Serve a single client until it disconnects or the actor terminates.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from or writing to the client fails, the client sends an
+    /// oversized payload, a payload cannot be transmitted to the NCP or the actor has shut down.
+    pub async fn serve<S>(&mut self, client: S) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        let (reader, mut writer) = tokio::io::split(client);
+        let mut requests = FramedRead::new(reader, codec());
+        let handle = &self.handle;
+        let payloads = &mut self.payloads;
+
+        let upstream = async {
+            while let Some(frame) = requests.next().await {
+                forward(handle, &frame?).await?;
+            }
+
+            Ok(())
+        };
+
+        let downstream = async {
+            let mut codec = codec();
+            let mut buffer = BytesMut::new();
+
+            while let Some(payload) = payloads.recv().await {
+                codec.encode(Bytes::copy_from_slice(&payload), &mut buffer)?;
+                writer.write_all_buf(&mut buffer).await?;
+            }
+
+            Err(io::Error::new(ErrorKind::BrokenPipe, Error::ActorShutDown))
+        };
+
+        let mut upstream = pin!(upstream);
+        let mut downstream = pin!(downstream);
+        poll_fn(|context| {
+            if let Poll::Ready(result) = upstream.as_mut().poll(context) {
+                return Poll::Ready(result);
+            }
+
+            downstream.as_mut().poll(context)
+        })
+        .await
+    }
+
+    /// Accept the next client while discarding payloads that have no recipient.
+    ///
+    /// Returns `None` if the actor has terminated.
+    async fn accept(
+        &mut self,
+    ) -> io::Result<Option<(tokio::net::TcpStream, std::net::SocketAddr)>> {
+        let mut accept = pin!(self.listener.accept());
+        let payloads = &mut self.payloads;
+
+        poll_fn(|context| {
+            if let Poll::Ready(result) = accept.as_mut().poll(context) {
+                return Poll::Ready(result.map(Some));
+            }
+
+            while let Poll::Ready(payload) = payloads.poll_recv(context) {
+                let Some(payload) = payload else {
+                    return Poll::Ready(Ok(None));
+                };
+
+                debug!("Discarding payload without client: {payload:#04X?}");
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+/// Send a client payload to the NCP.
+///
+/// Any transmission error is returned, so that the client is disconnected instead of silently
+/// losing the payload.
+async fn forward(handle: &Handle, frame: &[u8]) -> io::Result<()> {
+    let payload = Payload::from_slice(frame)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidData, "payload too large"))?;
+
+    handle.send(payload).await.map_err(io::Error::from)
+}
+
+/// Returns the codec of the length-prefixed framing.
+fn codec() -> LengthDelimitedCodec {
+    LengthDelimitedCodec::builder()
+        .length_field_type::<u16>()
+        .max_frame_length(MAX_PAYLOAD_SIZE)
+        .new_codec()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex};
+    use tokio::net::TcpListener;
+    use tokio::runtime::{Builder, Runtime};
+
+    use super::Gateway;
+    use crate::types::Payload;
+    use crate::virtual_ncp;
+
+    const BUFFER_SIZE: usize = 1024;
+
+    fn runtime() -> Runtime {
+        Builder::new_current_thread()
+            .enable_io()
+            .enable_time()
+            .build()
+            .expect("runtime should build")
+    }
+
+    #[test]
+    fn client_exchanges_payloads_with_ncp() {
+        runtime().block_on(async {
+            let (host, host_rx, mut ncp) = virtual_ncp::connect();
+            let listener = TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("listener should bind");
+            let addr = listener
+                .local_addr()
+                .expect("listener should have an address");
+            tokio::spawn(Gateway::new(listener, host, host_rx).run());
+            let mut client = tokio::net::TcpStream::connect(addr)
+                .await
+                .expect("client should connect");
+
+            client
+                .write_all(&[0x00, 0x03, 0x00, 0x00, 0x02])
+                .await
+                .expect("client should send");
+            let request: Payload = [0x00, 0x00, 0x02].into_iter().collect();
+            assert_eq!(ncp.receive().await, Some(request));
+
+            let response: Payload = [0x00, 0x80, 0x00, 0x02].into_iter().collect();
+            ncp.send(response).await.expect("NCP should send");
+            let mut frame = [0; 6];
+            client
+                .read_exact(&mut frame)
+                .await
+                .expect("client should receive");
+            assert_eq!(frame, [0x00, 0x04, 0x00, 0x80, 0x00, 0x02]);
+        });
+    }
+
+    #[test]
+    fn oversized_payload_disconnects_client() {
+        runtime().block_on(async {
+            let (host, host_rx, _ncp) = virtual_ncp::connect();
+            let listener = TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("listener should bind");
+            let mut gateway = Gateway::new(listener, host, host_rx);
+            let (client, mut remote) = duplex(BUFFER_SIZE);
+            remote
+                .write_all(&[0xFF, 0xFF])
+                .await
+                .expect("client should send");
+
+            let error = gateway
+                .serve(client)
+                .await
+                .expect_err("oversized payload should be rejected");
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        });
+    }
+}
@@ -13,6 +13,9 @@
 //! The returned [`Futures`] contains the transmitter and receiver futures. The caller must spawn
 //! or otherwise poll both futures on an async runtime.
 //!
+//! [`start_with_observer`] additionally accepts an [`observer::Observer`], which is notified of
+//! every frame the actor sends or receives.
+//!
 //! # Termination
 //!
 //! The actor does not use a terminate message. Drop every clone of [`Handle`] to close the
@@ -20,51 +23,125 @@
 //! the shared running state when it exits, which causes the receiver to terminate as well.
 //! Continue polling or awaiting both actor futures until they complete.
 //!
+//! # `no_std` support
+//!
+//! The frame, byte stuffing, masking and CRC layers in [`frame`], [`protocol`] and [`Validate`]
+//! do not allocate and are available under `#![no_std]`. Disable the default `std` feature to
+//! build them without the standard library. The actor, its [`Handle`] and all Tokio-based I/O
+//! require the `std` feature.
+//!
+//! The optional `embedded-io-async` feature provides frame readers and writers in [`embedded`]
+//! for transports implementing the `embedded-io-async` traits, e.g. a UART on a
+//! microcontroller.
+//!
 //! # EZSP integration
 //!
 //! The optional `ezsp` feature provides [`ezsp::Transmitter`] and [`ezsp::Receiver`] adapters.
-//! [`ezsp::Transmitter`] is an alias for [`Handle`], which implements `ezsp::Transmit`.
+//! [`Handle`] implements `ezsp::Transmit` itself, while [`ezsp::Transmitter`] wraps a [`Handle`]
+//! and adapts frame headers to the negotiated EZSP version.
 //! [`ezsp::Receiver`] consumes the channel of inbound [`Payload`] values passed to [`start`] and
 //! implements `ezsp::Receive`.
 //!
 //! You can find the protocol's definition on [siliconlabs.com](https://docs.silabs.com/zigbee/latest/uart-gateway-protocol-reference/).
 //!
 //! This library is free software and is not affiliated with Silicon Labs.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![deny(unsafe_code)]
 
+#[cfg(feature = "cli")]
+use clap as _;
 use const_env::env_item;
+#[cfg(all(test, not(feature = "std")))]
+use tokio as _;
 
-pub use self::actor::{Futures, Handle, start};
-pub use self::types::Payload;
+#[cfg(feature = "std")]
+pub use self::actor::{
+    Futures, Handle, TransportFactory, start, start_with_factory, start_with_factory_and_observer,
+    start_with_observer,
+};
+pub use self::code::Code;
+#[cfg(feature = "std")]
+pub use self::error::Error;
+#[cfg(feature = "std")]
+pub use self::stats::{CrcFailures, Stats};
+pub use self::types::{MAX_FRAME_SIZE, Payload, RawFrame};
+pub use self::validate::{CRC, Validate};
 
 /// Maximum payload size in bytes.
 #[env_item("ASHV2_MAX_PAYLOAD_SIZE")]
 pub const MAX_PAYLOAD_SIZE: usize = 128;
 
+#[cfg(feature = "std")]
 #[env_item("ASHV2_T_RSTACK_MAX_MILLIS")]
 const T_RSTACK_MAX_MILLIS: u64 = 3200;
 
 /// The amount of maximum unacknowledged frames that the NCP (or Host) can hold.
 /// Also amounts to the so-called *sliding window size*.
+#[cfg(feature = "std")]
 #[env_item("ASHV2_TX_K")]
 const TX_K: usize = 5;
 
+#[cfg(feature = "std")]
 #[env_item("ASHV2_T_RX_ACK_MAX_MILLIS")]
 const T_RX_ACK_MAX_MILLIS: u64 = 3200;
 
 const VERSION: u8 = 0x02;
 
+#[cfg(feature = "std")]
 const SEQ_MASK: u8 = 0b0000_0111;
 
+#[cfg(feature = "std")]
 mod actor;
+#[cfg(feature = "std")]
+pub mod bootloader;
 mod code;
+#[cfg(feature = "std")]
+mod deframer;
+#[cfg(feature = "embedded-io-async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embedded-io-async")))]
+pub mod embedded;
+#[cfg(feature = "std")]
+mod error;
 #[cfg(feature = "ezsp")]
 #[cfg_attr(docsrs, doc(cfg(feature = "ezsp")))]
 pub mod ezsp;
-mod frame;
+#[cfg(feature = "fault-injection")]
+#[cfg_attr(docsrs, doc(cfg(feature = "fault-injection")))]
+pub mod fault_injection;
+pub mod frame;
+#[cfg(feature = "gateway")]
+#[cfg_attr(docsrs, doc(cfg(feature = "gateway")))]
+pub mod gateway;
 mod hex_slice;
-mod protocol;
+#[cfg(feature = "std")]
+pub mod mux;
+#[cfg(feature = "std")]
+pub mod ncp;
+#[cfg(feature = "std")]
+pub mod observer;
+#[cfg(feature = "pcapng")]
+#[cfg_attr(docsrs, doc(cfg(feature = "pcapng")))]
+pub mod pcapng;
+#[cfg(feature = "std")]
+pub mod probe;
+pub mod protocol;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "serial")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serial")))]
+pub mod serial;
+#[cfg(feature = "std")]
+pub mod sniffer;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
 mod status;
+#[cfg(feature = "tcp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tcp")))]
+pub mod tcp;
 mod types;
 mod validate;
+#[cfg(any(feature = "virtual-ncp", all(test, feature = "std")))]
+#[cfg_attr(docsrs, doc(cfg(feature = "virtual-ncp")))]
+pub mod virtual_ncp;
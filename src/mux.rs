@@ -0,0 +1,460 @@
+//! Multiplexer that shares one `ASHv2` connection between several clients.
+//!
+//! A [`Handle`] serializes outbound payloads by itself, but inbound payloads are delivered to the
+//! single response channel passed to [`crate::start`]. [`start`] takes over that channel and
+//! returns a [`Multiplexer`], from which any number of [`Client`]s can be subscribed, and a
+//! dispatcher future that the caller must spawn or poll.
+//!
+//! Outbound payloads of all clients are enqueued one after another. Before a payload is enqueued,
+//! the [`Route`] is told which client sent it, and if it cannot be sent, the route is told to
+//! forget it again. For every inbound payload, the route decides which
+//! clients receive it. [`SequenceRoute`] routes EZSP responses to the client that sent the
+//! command with the same sequence number and broadcasts callbacks to all clients, while
+//! [`Broadcast`] delivers every payload to every client.
+//!
+//! The dispatcher waits until each recipient has room for a payload, so a client that does not
+//! consume its payloads throttles the connection. Drop clients that are no longer in use.
+//!
+//! ```no_run
+//! use ashv2::mux::{self, SequenceRoute};
+//! use tokio::sync::mpsc::channel;
+//!
+//! # async fn run(reader: tokio::io::DuplexStream, writer: tokio::io::DuplexStream) -> Result<(), ashv2::Error> {
+//! let (response_tx, response_rx) = channel(64);
+//! let (handle, futures) = ashv2::start(reader, writer, response_tx);
+//! tokio::spawn(futures.transmitter);
+//! tokio::spawn(futures.receiver);
+//!
+//! let (multiplexer, dispatcher) = mux::start(handle, response_rx, SequenceRoute::default());
+//! tokio::spawn(dispatcher);
+//!
+//! let mut stack = multiplexer.subscribe(16);
+//! let mut diagnostics = multiplexer.subscribe(16);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use log::{debug, trace, warn};
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+
+use crate::Handle;
+use crate::error::Error;
+use crate::types::Payload;
+
+/// Bit of the EZSP frame control low byte that marks a response.
+const RESPONSE: u8 = 0b1000_0000;
+
+/// Bits of the EZSP frame control low byte that mark a callback.
+const CALLBACK_TYPE: u8 = 0b0001_1000;
+
+/// Identifier of a [`Client`] of a [`Multiplexer`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ClientId(u64);
+
+impl Display for ClientId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Recipients of an inbound payload.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Recipients {
+    /// Deliver the payload to no client.
+    None,
+    /// Deliver the payload to the given client.
+    One(ClientId),
+    /// Deliver the payload to all clients.
+    All,
+}
+
+/// Routing of inbound payloads to the clients of a [`Multiplexer`].
+pub trait Route: Send {
+    /// Record that `client` is about to send `payload` to the NCP.
+    fn outbound(&mut self, client: ClientId, payload: &Payload);
+
+    /// Return the recipients of `payload` received from the NCP.
+    fn inbound(&mut self, payload: &Payload) -> Recipients;
+
+    /// Forget about `payload` of `client`, which could not be sent to the NCP.
+    fn failed(&mut self, client: ClientId, payload: &Payload) {
+        let _ = (client, payload);
+    }
+
+    /// Forget about `client`, which has been dropped.
+    fn disconnected(&mut self, client: ClientId) {
+        let _ = client;
+    }
+}
+
+/// Route that delivers every inbound payload to all clients.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Broadcast;
+
+impl Route for Broadcast {
+    fn outbound(&mut self, _client: ClientId, _payload: &Payload) {}
+
+    fn inbound(&mut self, _payload: &Payload) -> Recipients {
+        Recipients::All
+    }
+}
+
+/// Route that matches EZSP responses to commands by their sequence number.
+///
+/// The sequence number is the first byte of both legacy and extended EZSP headers and the frame
+/// control low byte is the second one. Responses are delivered to the client that most recently
+/// sent a command with the same sequence number. Callbacks are broadcast to all clients.
+/// Responses to unknown sequence numbers are discarded.
+///
+/// Clients should use disjoint sequence numbers, e.g. by sharing a counter, since a command
+/// with a sequence number that is still pending takes over its response.
+#[derive(Clone, Debug)]
+pub struct SequenceRoute {
+    pending: [Option<ClientId>; 256],
+}
+
+impl Default for SequenceRoute {
+    fn default() -> Self {
+        Self {
+            pending: [None; 256],
+        }
+    }
+}
+
+impl Route for SequenceRoute {
+    fn outbound(&mut self, client: ClientId, payload: &Payload) {
+        if let Some(&sequence) = payload.first()
+            && let Some(previous) = self.pending[usize::from(sequence)].replace(client)
+            && previous != client
+        {
+            warn!("Client {client} reuses sequence number {sequence} pending for {previous}.");
+        }
+    }
+
+    fn failed(&mut self, client: ClientId, payload: &Payload) {
+        if let Some(&sequence) = payload.first() {
+            let pending = &mut self.pending[usize::from(sequence)];
+
+            if *pending == Some(client) {
+                pending.take();
+            }
+        }
+    }
+
+    fn inbound(&mut self, payload: &Payload) -> Recipients {
+        let [sequence, frame_control, ..] = payload.as_slice() else {
+            warn!("Broadcasting payload without EZSP header: {payload:#04X?}");
+            return Recipients::All;
+        };
+
+        if frame_control & RESPONSE == 0 || frame_control & CALLBACK_TYPE != 0 {
+            return Recipients::All;
+        }
+
+        self.pending[usize::from(*sequence)].take().map_or_else(
+            || {
+                debug!("Discarding response with unknown sequence number {sequence}.");
+                Recipients::None
+            },
+            Recipients::One,
+        )
+    }
+
+    fn disconnected(&mut self, client: ClientId) {
+        self.pending
+            .iter_mut()
+            .filter(|pending| **pending == Some(client))
+            .for_each(|pending| *pending = None);
+    }
+}
+
+/// State shared between the clients and the dispatcher.
+struct Shared {
+    route: Mutex<Box<dyn Route>>,
+    subscribers: Mutex<BTreeMap<ClientId, Sender<Payload>>>,
+}
+
+impl Shared {
+    fn route(&self) -> MutexGuard<'_, Box<dyn Route>> {
+        self.route.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn subscribers(&self) -> MutexGuard<'_, BTreeMap<ClientId, Sender<Payload>>> {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn unsubscribe(&self, client: ClientId) {
+        self.subscribers().remove(&client);
+        self.route().disconnected(client);
+    }
+}
+
+/// State of the sending side, which keeps the actor alive.
+struct Outbound {
+    handle: Handle,
+    shared: Arc<Shared>,
+    next_id: AtomicU64,
+    sending: tokio::sync::Mutex<()>,
+}
+
+/// Shares an `ASHv2` connection between several [`Client`]s.
+#[derive(Clone)]
+pub struct Multiplexer {
+    outbound: Arc<Outbound>,
+}
+
+impl Multiplexer {
+    /// Subscribe a new client whose inbound payload queue holds up to `capacity` payloads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    #[must_use]
+    pub fn subscribe(&self, capacity: usize) -> Client {
+        let (sender, payloads) = channel(capacity);
+        let id = ClientId(self.outbound.next_id.fetch_add(1, Relaxed));
+        self.outbound.shared.subscribers().insert(id, sender);
+        debug!("Client {id} subscribed.");
+        Client {
+            id,
+            outbound: self.outbound.clone(),
+            payloads,
+        }
+    }
+}
+
+impl Debug for Multiplexer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Multiplexer").finish_non_exhaustive()
+    }
+}
+
+/// A client of a [`Multiplexer`].
+///
+/// Dropping the client unsubscribes it.
+pub struct Client {
+    id: ClientId,
+    outbound: Arc<Outbound>,
+    payloads: Receiver<Payload>,
+}
+
+impl Client {
+    /// Returns the identifier of the client.
+    #[must_use]
+    pub const fn id(&self) -> ClientId {
+        self.id
+    }
+
+    /// Send a payload to the NCP after the payloads of other clients that are already waiting.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the payload could not be transmitted.
+    pub async fn send(&self, payload: Payload) -> Result<(), Error> {
+        let result = self.submit(payload.clone()).await;
+
+        if result.is_err() {
+            self.outbound.shared.route().failed(self.id, &payload);
+        }
+
+        result
+    }
+
+    /// Route and enqueue a payload and wait for its transmission.
+    ///
+    /// Only routing and enqueueing are serialized with other clients, so that the route sees
+    /// payloads in the order in which they are sent.
+    async fn submit(&self, payload: Payload) -> Result<(), Error> {
+        let response = {
+            let _sending = self.outbound.sending.lock().await;
+            self.outbound.shared.route().outbound(self.id, &payload);
+            self.outbound.handle.submit(payload).await?
+        };
+
+        response.await.map_err(|_| Error::ActorShutDown)?.map(drop)
+    }
+
+    /// Receive the next payload routed to this client.
+    ///
+    /// Returns `None` once the actor has terminated.
+    pub async fn recv(&mut self) -> Option<Payload> {
+        self.payloads.recv().await
+    }
+}
+
+impl Debug for Client {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        debug!("Client {} unsubscribed.", self.id);
+        self.outbound.shared.unsubscribe(self.id);
+    }
+}
+
+/// Create a multiplexer over the actor's `handle` and the receiving end of its response channel.
+///
+/// Returns the [`Multiplexer`] to subscribe clients and the dispatcher future that routes inbound
+/// payloads to them. The caller must spawn or otherwise poll the dispatcher. It terminates once
+/// the actor has terminated, i.e. after the multiplexer and all clients have been dropped.
+pub fn start<R>(
+    handle: Handle,
+    inbound: Receiver<Payload>,
+    route: R,
+) -> (Multiplexer, impl Future<Output = ()> + Send + 'static)
+where
+    R: Route + 'static,
+{
+    let shared = Arc::new(Shared {
+        route: Mutex::new(Box::new(route)),
+        subscribers: Mutex::new(BTreeMap::new()),
+    });
+    let multiplexer = Multiplexer {
+        outbound: Arc::new(Outbound {
+            handle,
+            shared: shared.clone(),
+            next_id: AtomicU64::new(0),
+            sending: tokio::sync::Mutex::new(()),
+        }),
+    };
+    (multiplexer, dispatch(inbound, shared))
+}
+
+/// Deliver inbound payloads to the clients selected by the route.
+async fn dispatch(mut inbound: Receiver<Payload>, shared: Arc<Shared>) {
+    while let Some(payload) = inbound.recv().await {
+        let recipients = shared.route().inbound(&payload);
+        trace!("Routing payload {payload:#04X?} to {recipients:?}");
+        let senders: Vec<_> = {
+            let subscribers = shared.subscribers();
+
+            match recipients {
+                Recipients::None => Vec::new(),
+                Recipients::One(client) => subscribers
+                    .get(&client)
+                    .map(|sender| (client, sender.clone()))
+                    .into_iter()
+                    .collect(),
+                Recipients::All => subscribers
+                    .iter()
+                    .map(|(client, sender)| (*client, sender.clone()))
+                    .collect(),
+            }
+        };
+
+        for (client, sender) in senders {
+            if sender.send(payload.clone()).await.is_err() {
+                debug!("Client {client} has gone away.");
+                shared.unsubscribe(client);
+            }
+        }
+    }
+
+    debug!("Multiplexer dispatcher terminated.");
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::runtime::Builder;
+
+    use super::{ClientId, Recipients, Route, SequenceRoute, start};
+    use crate::types::Payload;
+    use crate::virtual_ncp;
+
+    const CHANNEL_SIZE: usize = 8;
+
+    fn payload(bytes: &[u8]) -> Payload {
+        Payload::from_slice(bytes).expect("payload should fit")
+    }
+
+    #[test]
+    fn sequence_route_matches_responses_and_broadcasts_callbacks() {
+        let mut route = SequenceRoute::default();
+        route.outbound(ClientId(0), &payload(&[0x01, 0x00, 0x55]));
+        route.outbound(ClientId(1), &payload(&[0x02, 0x00, 0x55]));
+
+        assert_eq!(
+            route.inbound(&payload(&[0x02, 0x80, 0x55])),
+            Recipients::One(ClientId(1))
+        );
+        assert_eq!(
+            route.inbound(&payload(&[0x02, 0x80, 0x55])),
+            Recipients::None
+        );
+        assert_eq!(
+            route.inbound(&payload(&[0x07, 0x90, 0x19])),
+            Recipients::All
+        );
+
+        route.disconnected(ClientId(0));
+        assert_eq!(
+            route.inbound(&payload(&[0x01, 0x80, 0x55])),
+            Recipients::None
+        );
+
+        route.outbound(ClientId(0), &payload(&[0x03, 0x00, 0x55]));
+        route.outbound(ClientId(1), &payload(&[0x04, 0x00, 0x55]));
+        route.failed(ClientId(1), &payload(&[0x03, 0x00, 0x55]));
+        route.failed(ClientId(1), &payload(&[0x04, 0x00, 0x55]));
+        assert_eq!(
+            route.inbound(&payload(&[0x03, 0x80, 0x55])),
+            Recipients::One(ClientId(0))
+        );
+        assert_eq!(
+            route.inbound(&payload(&[0x04, 0x80, 0x55])),
+            Recipients::None
+        );
+    }
+
+    #[test]
+    fn clients_share_connection() {
+        Builder::new_current_thread()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (host, host_rx, mut ncp) = virtual_ncp::connect();
+                let (multiplexer, dispatcher) = start(host, host_rx, SequenceRoute::default());
+                tokio::spawn(dispatcher);
+                let mut stack = multiplexer.subscribe(CHANNEL_SIZE);
+                let mut updater = multiplexer.subscribe(CHANNEL_SIZE);
+
+                let command = payload(&[0x01, 0x00, 0x55]);
+                stack
+                    .send(command.clone())
+                    .await
+                    .expect("stack should send");
+                assert_eq!(ncp.receive().await, Some(command));
+                let command = payload(&[0x02, 0x00, 0x55]);
+                updater
+                    .send(command.clone())
+                    .await
+                    .expect("updater should send");
+                assert_eq!(ncp.receive().await, Some(command));
+
+                let response = payload(&[0x02, 0x80, 0x55, 0x00]);
+                ncp.send(response.clone()).await.expect("NCP should send");
+                assert_eq!(updater.recv().await, Some(response));
+                let response = payload(&[0x01, 0x80, 0x55, 0x00]);
+                ncp.send(response.clone()).await.expect("NCP should send");
+                assert_eq!(stack.recv().await, Some(response));
+
+                let callback = payload(&[0x00, 0x90, 0x19, 0x00]);
+                ncp.send(callback.clone()).await.expect("NCP should send");
+                assert_eq!(stack.recv().await, Some(callback.clone()));
+                assert_eq!(updater.recv().await, Some(callback));
+            });
+    }
+}
@@ -0,0 +1,104 @@
+//! NCP-side (device role) implementation of `ASHv2`.
+//!
+//! [`start`] creates actor futures that play the NCP's part of the protocol over the given
+//! transport: they wait for the host's `RST` frame and answer it with an `RST_ACK` frame carrying
+//! the configured reset [`Code`], acknowledge or reject inbound `DATA` frames with `ACK` or `NAK`
+//! frames and transmit outbound `DATA` frames within their own sliding window.
+//!
+//! This allows building NCP emulators and test fixtures for host implementations.
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc::Sender;
+
+pub use self::handle::Handle;
+use crate::Futures;
+use crate::actor::{Role, create};
+use crate::code::Code;
+use crate::observer::Tap;
+use crate::types::Payload;
+
+mod handle;
+
+/// Create the NCP-side `ASHv2` actor futures for the given asynchronous reader and writer.
+///
+/// The response channel receives inbound `DATA` payloads from the host. Its capacity is also
+/// used for the actor's internal message queue. Every `RST` frame received from the host is
+/// answered with an `RST_ACK` frame carrying `reset_code`.
+///
+/// Returns the NCP [`Handle`] and named [`Futures`] that the caller must spawn or otherwise poll
+/// on their async runtime. Termination follows the same rules as for [`crate::start`].
+pub fn start<R, W>(
+    reader: R,
+    writer: W,
+    response: Sender<Payload>,
+    reset_code: Code,
+) -> (
+    Handle,
+    Futures<impl Future<Output = ()> + Send + 'static, impl Future<Output = ()> + Send + 'static>,
+)
+where
+    R: AsyncRead + Send + Sync + Unpin + 'static,
+    W: AsyncWrite + Send + Sync + Unpin + 'static,
+{
+    let (handle, futures) = create(
+        reader,
+        writer,
+        response,
+        Role::Ncp(reset_code),
+        Tap::default(),
+        None,
+    );
+    (Handle::new(handle), futures)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, split};
+    use tokio::runtime::Builder;
+    use tokio::sync::mpsc::channel;
+
+    use crate::code::Code;
+    use crate::types::Payload;
+
+    const BUFFER_SIZE: usize = 1024;
+    const CHANNEL_SIZE: usize = 8;
+
+    #[test]
+    fn host_and_ncp_exchange_payloads() {
+        Builder::new_current_thread()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (host_stream, ncp_stream) = duplex(BUFFER_SIZE);
+                let (host_reader, host_writer) = split(host_stream);
+                let (ncp_reader, ncp_writer) = split(ncp_stream);
+                let (host_tx, mut host_rx) = channel(CHANNEL_SIZE);
+                let (ncp_tx, mut ncp_rx) = channel(CHANNEL_SIZE);
+                let (host, host_futures) = crate::start(host_reader, host_writer, host_tx);
+                let (ncp, ncp_futures) =
+                    super::start(ncp_reader, ncp_writer, ncp_tx, Code::Software);
+                tokio::spawn(host_futures.transmitter);
+                tokio::spawn(host_futures.receiver);
+                tokio::spawn(ncp_futures.transmitter);
+                tokio::spawn(ncp_futures.receiver);
+
+                let request: Payload = [0x00, 0x00, 0x00, 0x02].into_iter().collect();
+                host.send(request.clone()).await.expect("host should send");
+                assert_eq!(ncp_rx.recv().await, Some(request));
+
+                let response: Payload = [0x00, 0x80, 0x00, 0x02, 0x02, 0x11, 0x30]
+                    .into_iter()
+                    .collect();
+                ncp.send(response.clone()).await.expect("NCP should send");
+                assert_eq!(host_rx.recv().await, Some(response));
+
+                // The link keeps working after the host reset it in response to an ERROR frame.
+                ncp.send_error(Code::ExceededMaximumAckTimeoutCount)
+                    .await
+                    .expect("NCP should send ERROR");
+                let request: Payload = [0x01, 0x00, 0x00, 0x04].into_iter().collect();
+                host.send(request.clone()).await.expect("host should send");
+                assert_eq!(ncp_rx.recv().await, Some(request));
+            });
+    }
+}
@@ -0,0 +1,51 @@
+//! Handle for the NCP-side `ASHv2` actor.
+
+use crate::Payload;
+use crate::actor::Message;
+use crate::code::Code;
+use crate::error::Error;
+use crate::stats::Stats;
+
+/// User-facing handle for the NCP-side `ASHv2` actor.
+///
+/// The handle behaves like [`crate::Handle`] and can additionally emit `ERROR` frames.
+#[derive(Clone, Debug)]
+pub struct Handle {
+    inner: crate::Handle,
+}
+
+impl Handle {
+    /// Create an NCP handle that sends messages through the given actor handle.
+    pub(crate) const fn new(inner: crate::Handle) -> Self {
+        Self { inner }
+    }
+
+    /// Send data to the host.
+    ///
+    /// Payloads sent before the host has reset the connection remain queued.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ActorShutDown`] if the actor futures are no longer accepting messages or
+    /// another [`Error`] if the transmitter failed to write the payload.
+    pub async fn send(&self, payload: Payload) -> Result<(), Error> {
+        self.inner.send(payload).await
+    }
+
+    /// Send an `ERROR` frame with the given code to the host.
+    ///
+    /// The NCP enters the failed state and waits for the host to reset the connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ActorShutDown`] if the actor futures are no longer accepting messages.
+    pub async fn send_error(&self, code: Code) -> Result<(), Error> {
+        self.inner.enqueue(Message::SendError(code)).await
+    }
+
+    /// Return a snapshot of the link statistics.
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        self.inner.stats()
+    }
+}
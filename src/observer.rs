@@ -0,0 +1,188 @@
+//! Taps on the frames sent and received by the `ASHv2` actor.
+//!
+//! Pass an [`Observer`] to [`crate::start_with_observer`] to have it invoked with a
+//! [`FrameEvent`] for every frame the actor writes to or reads from its transport. Received
+//! frames are reported before their CRC is validated, so observers also see corrupted frames.
+//! Received bytes that cannot be decoded into a frame are reported with the corresponding
+//! [`DecodeError`].
+//!
+//! Observers are called synchronously from the actor futures and should return quickly.
+
+use core::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::frame::{DecodeError, Frame};
+
+/// Direction in which a frame passed the transport.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Direction {
+    /// The frame was written to the transport.
+    Sent,
+
+    /// The frame was read from the transport.
+    Received,
+}
+
+/// A frame sent or received by the actor.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameEvent<'a> {
+    /// Whether the frame was sent or received.
+    pub direction: Direction,
+
+    /// The time at which the frame was written or completely read.
+    pub timestamp: SystemTime,
+
+    /// The decoded frame or the reason why it could not be decoded.
+    pub frame: Result<&'a Frame, DecodeError>,
+
+    /// The stuffed bytes of the frame as transferred, including the terminating `FLAG` byte.
+    pub bytes: &'a [u8],
+}
+
+/// Observer of the frames sent and received by the actor.
+pub trait Observer: Send + Sync + 'static {
+    /// Handle a frame sent or received by the actor.
+    fn observe(&self, event: &FrameEvent<'_>);
+}
+
+impl<T> Observer for Arc<T>
+where
+    T: Observer + ?Sized,
+{
+    fn observe(&self, event: &FrameEvent<'_>) {
+        (**self).observe(event);
+    }
+}
+
+/// The unit observer ignores all frames.
+impl Observer for () {
+    fn observe(&self, _event: &FrameEvent<'_>) {}
+}
+
+/// Shared observer invoked by the actor futures.
+#[derive(Clone, Default)]
+pub(crate) struct Tap(Option<Arc<dyn Observer>>);
+
+impl Tap {
+    /// Create a tap invoking the given observer.
+    pub fn new<O>(observer: O) -> Self
+    where
+        O: Observer,
+    {
+        Self(Some(Arc::new(observer)))
+    }
+
+    /// Return `true` if an observer is attached to the tap.
+    pub const fn is_attached(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Report a frame to the observer, if any.
+    pub fn notify(&self, direction: Direction, frame: Result<&Frame, DecodeError>, bytes: &[u8]) {
+        if let Some(observer) = &self.0 {
+            observer.observe(&FrameEvent {
+                direction,
+                timestamp: SystemTime::now(),
+                frame,
+                bytes,
+            });
+        }
+    }
+}
+
+impl Debug for Tap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Tap").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex, split};
+    use tokio::runtime::Builder;
+    use tokio::sync::mpsc::channel;
+    use tokio::task::yield_now;
+
+    use super::{Direction, FrameEvent, Observer};
+    use crate::frame::{DecodeError, Frame, RST};
+
+    type Events = Arc<Mutex<Vec<(Direction, Result<Frame, DecodeError>, Vec<u8>)>>>;
+
+    #[derive(Clone, Default)]
+    struct Collector(Events);
+
+    impl Observer for Collector {
+        fn observe(&self, event: &FrameEvent<'_>) {
+            self.0.lock().expect("lock should not be poisoned").push((
+                event.direction,
+                event.frame.cloned(),
+                event.bytes.to_vec(),
+            ));
+        }
+    }
+
+    #[test]
+    fn observes_sent_and_invalid_received_frames() {
+        const BAD_ACK: [u8; 4] = [0x81, 0x60, 0x00, 0x7E];
+        const UNKNOWN: [u8; 4] = [0xFE, 0x00, 0x00, 0x7E];
+
+        Builder::new_current_thread()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (host, mut ncp) = duplex(1024);
+                let (reader, writer) = split(host);
+                let (response, _payloads) = channel(8);
+                let collector = Collector::default();
+                let (_handle, futures) =
+                    crate::start_with_observer(reader, writer, response, collector.clone());
+                tokio::spawn(futures.transmitter);
+                tokio::spawn(futures.receiver);
+
+                let mut rst = [0; 4];
+                ncp.read_exact(&mut rst)
+                    .await
+                    .expect("host should send RST");
+                ncp.write_all(&BAD_ACK)
+                    .await
+                    .expect("bytes should be written");
+                ncp.write_all(&UNKNOWN)
+                    .await
+                    .expect("bytes should be written");
+
+                while collector
+                    .0
+                    .lock()
+                    .expect("lock should not be poisoned")
+                    .len()
+                    < 3
+                {
+                    yield_now().await;
+                }
+
+                let events = collector
+                    .0
+                    .lock()
+                    .expect("lock should not be poisoned")
+                    .clone();
+                assert_eq!(
+                    events[0],
+                    (Direction::Sent, Ok(Frame::Rst(RST)), rst.to_vec())
+                );
+                assert_eq!(events[1].0, Direction::Received);
+                assert!(matches!(events[1].1, Ok(Frame::Ack(_))));
+                assert_eq!(events[1].2, BAD_ACK);
+                assert_eq!(
+                    events[2],
+                    (
+                        Direction::Received,
+                        Err(DecodeError::UnknownHeader(0xFE)),
+                        UNKNOWN.to_vec()
+                    )
+                );
+            });
+    }
+}
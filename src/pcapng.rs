@@ -0,0 +1,244 @@
+//! Capture of `ASHv2` traffic in the pcapng format for Wireshark.
+//!
+//! This module is available with the `pcapng` crate feature. [`Writer`] writes a pcapng section
+//! with a single interface and one Enhanced Packet Block per frame. It implements
+//! [`Observer`] and can be passed to [`crate::start_with_observer`]
+//! directly or, to retain access to the writer, wrapped in an [`Arc`](std::sync::Arc).
+//!
+//! # Record layout
+//!
+//! All blocks are written in little-endian byte order.
+//!
+//! * The Section Header Block has version 1.0 and an unspecified section length.
+//! * The Interface Description Block uses the link type `LINKTYPE_USER0` (147), an unlimited
+//!   snapshot length and the default timestamp resolution of microseconds.
+//! * Each frame is written as an Enhanced Packet Block on interface 0, timestamped with the
+//!   microseconds since the Unix epoch at which the frame was written or read. Its `epb_flags`
+//!   option (code 2) carries the direction in bits 0-1: `0b01` for frames received from the peer
+//!   and `0b10` for frames sent to the peer.
+//!
+//! The packet data of each Enhanced Packet Block is the frame as transferred, with byte stuffing
+//! reverted and without the terminating `FLAG` byte:
+//!
+//! | Frame     | Header byte  | Body                                  | Trailer    |
+//! |-----------|--------------|---------------------------------------|------------|
+//! | `DATA`    | `0xxx_xxxx`  | masked payload                        | CRC (BE)   |
+//! | `ACK`     | `100x_xxxx`  |                                       | CRC (BE)   |
+//! | `NAK`     | `101x_xxxx`  |                                       | CRC (BE)   |
+//! | `RST`     | `0xC0`       |                                       | CRC (BE)   |
+//! | `RST_ACK` | `0xC1`       | version, reset code                   | CRC (BE)   |
+//! | `ERROR`   | `0xC2`       | version, error code                   | CRC (BE)   |
+//!
+//! The CRC is CRC-16/IBM-3740 (CCITT, initial value `0xFFFF`) over the header and body. To
+//! unmask a `DATA` payload, XOR its bytes with the pseudo-random sequence starting at `0x42`,
+//! where each next value is the previous value shifted right by one bit and, if the previous
+//! value's least significant bit was set, combined with `0xB8` by exclusive or.
+//!
+//! Frames that could not be decoded are captured as well, so a dissector should expect
+//! truncated frames and unknown headers.
+
+use std::io::{self, Write};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::error;
+
+use crate::observer::{Direction, FrameEvent, Observer};
+use crate::protocol::{ControlByte, Unstuff};
+
+/// The link type for user-defined encapsulations used for `ASHv2` frames.
+pub const LINKTYPE_USER0: u16 = 147;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const EPB_FLAGS: u16 = 2;
+const EPB_FLAGS_INBOUND: u32 = 0b01;
+const EPB_FLAGS_OUTBOUND: u32 = 0b10;
+const OPT_ENDOFOPT: u16 = 0;
+
+/// Writes `ASHv2` frames to a pcapng capture.
+#[derive(Debug)]
+pub struct Writer<W> {
+    inner: Mutex<W>,
+}
+
+impl<W> Writer<W>
+where
+    W: Write,
+{
+    /// Create a new capture writer and write the section and interface headers to `inner`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the headers could not be written.
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        write_block(&mut inner, SECTION_HEADER_BLOCK, |body| {
+            body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+            body.extend_from_slice(&1u16.to_le_bytes());
+            body.extend_from_slice(&0u16.to_le_bytes());
+            body.extend_from_slice(&(-1i64).to_le_bytes());
+        })?;
+        write_block(&mut inner, INTERFACE_DESCRIPTION_BLOCK, |body| {
+            body.extend_from_slice(&LINKTYPE_USER0.to_le_bytes());
+            body.extend_from_slice(&0u16.to_le_bytes());
+            body.extend_from_slice(&0u32.to_le_bytes());
+        })?;
+        inner.flush()?;
+        Ok(Self {
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Write a frame to the capture.
+    ///
+    /// `frame` is the packet data as described in the [module documentation](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame could not be written.
+    pub fn write_frame(
+        &self,
+        direction: Direction,
+        timestamp: SystemTime,
+        frame: &[u8],
+    ) -> io::Result<()> {
+        let micros = timestamp
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_micros());
+        let micros = u64::try_from(micros).unwrap_or(u64::MAX);
+        let length = u32::try_from(frame.len()).map_err(io::Error::other)?;
+        let flags = match direction {
+            Direction::Received => EPB_FLAGS_INBOUND,
+            Direction::Sent => EPB_FLAGS_OUTBOUND,
+        };
+
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        write_block(&mut *inner, ENHANCED_PACKET_BLOCK, |body| {
+            body.extend_from_slice(&0u32.to_le_bytes());
+            body.extend_from_slice(&micros.to_le_bytes()[4..]);
+            body.extend_from_slice(&micros.to_le_bytes()[..4]);
+            body.extend_from_slice(&length.to_le_bytes());
+            body.extend_from_slice(&length.to_le_bytes());
+            body.extend_from_slice(frame);
+            pad(body);
+            body.extend_from_slice(&EPB_FLAGS.to_le_bytes());
+            body.extend_from_slice(&4u16.to_le_bytes());
+            body.extend_from_slice(&flags.to_le_bytes());
+            body.extend_from_slice(&OPT_ENDOFOPT.to_le_bytes());
+            body.extend_from_slice(&0u16.to_le_bytes());
+        })?;
+        inner.flush()
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<W> Observer for Writer<W>
+where
+    W: Write + Send + 'static,
+{
+    fn observe(&self, event: &FrameEvent<'_>) {
+        let mut frame = event.bytes.to_vec();
+
+        if frame.last() == Some(&ControlByte::Flag.into()) {
+            frame.pop();
+        }
+
+        frame.unstuff();
+        self.write_frame(event.direction, event.timestamp, &frame)
+            .unwrap_or_else(|error| error!("Failed to write frame to capture: {error}"));
+    }
+}
+
+/// Write a block of the given type whose body is written by `body`.
+fn write_block<W, F>(writer: &mut W, block_type: u32, body: F) -> io::Result<()>
+where
+    W: Write + ?Sized,
+    F: FnOnce(&mut Vec<u8>),
+{
+    let mut block = Vec::new();
+    block.extend_from_slice(&block_type.to_le_bytes());
+    block.extend_from_slice(&0u32.to_le_bytes());
+    body(&mut block);
+    let length = u32::try_from(block.len() + 4).map_err(io::Error::other)?;
+    block[4..8].copy_from_slice(&length.to_le_bytes());
+    block.extend_from_slice(&length.to_le_bytes());
+    writer.write_all(&block)
+}
+
+/// Pad `body` with zeros to a multiple of four bytes.
+fn pad(body: &mut Vec<u8>) {
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{Direction, FrameEvent, LINKTYPE_USER0, Observer, Writer};
+    use crate::frame::{Frame, RST};
+
+    const HEADER_SIZE: usize = 28 + 20;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(
+            bytes[offset..offset + 4]
+                .try_into()
+                .expect("slice should have four bytes"),
+        )
+    }
+
+    #[test]
+    fn writes_section_and_interface_headers() {
+        let capture = Writer::new(Vec::new())
+            .expect("headers should be written")
+            .into_inner();
+        assert_eq!(capture.len(), HEADER_SIZE);
+        assert_eq!(u32_at(&capture, 0), 0x0A0D_0D0A);
+        assert_eq!(u32_at(&capture, 4), 28);
+        assert_eq!(u32_at(&capture, 8), 0x1A2B_3C4D);
+        assert_eq!(u32_at(&capture, 24), 28);
+        assert_eq!(u32_at(&capture, 28), 1);
+        assert_eq!(u32_at(&capture, 32), 20);
+        assert_eq!(
+            u16::from_le_bytes([capture[36], capture[37]]),
+            LINKTYPE_USER0
+        );
+        assert_eq!(u32_at(&capture, 44), 20);
+    }
+
+    #[test]
+    fn writes_unstuffed_frames_with_direction() {
+        let writer = Writer::new(Vec::new()).expect("headers should be written");
+        let timestamp = UNIX_EPOCH + Duration::from_micros(0x0001_0000_0002);
+        writer.observe(&FrameEvent {
+            direction: Direction::Received,
+            timestamp,
+            frame: Ok(&Frame::Rst(RST)),
+            bytes: &[0xC0, 0x38, 0xBC, 0x7E],
+        });
+        let capture = writer.into_inner();
+        let block = &capture[HEADER_SIZE..];
+
+        assert_eq!(u32_at(block, 0), 6);
+        assert_eq!(u32_at(block, 4), 48);
+        assert_eq!(u32_at(block, 8), 0);
+        assert_eq!(u32_at(block, 12), 1);
+        assert_eq!(u32_at(block, 16), 2);
+        assert_eq!(u32_at(block, 20), 3);
+        assert_eq!(u32_at(block, 24), 3);
+        assert_eq!(&block[28..32], [0xC0, 0x38, 0xBC, 0x00]);
+        assert_eq!(&block[32..36], [0x02, 0x00, 0x04, 0x00]);
+        assert_eq!(u32_at(block, 36), 0b01);
+        assert_eq!(u32_at(block, 40), 0);
+        assert_eq!(u32_at(block, 44), 48);
+        assert_eq!(block.len(), 48);
+    }
+}
@@ -0,0 +1,255 @@
+//! Detection of the protocol spoken on a transport.
+//!
+//! [`probe`] finds out whether an NCP on a transport is in `ASHv2` mode, runs the Gecko
+//! bootloader, or does not respond at all. It sends a `CANCEL` byte and an `RST` frame and waits
+//! for an `ASHv2` `RST_ACK` frame. If none arrives in time, it requests the bootloader menu with a
+//! line break and waits for the bootloader prompt.
+//!
+//! The probe resets an NCP in `ASHv2` mode, so it should only be used before starting the actor.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use ashv2::probe::{Probe, probe};
+//!
+//! # async fn run(mut port: tokio::io::DuplexStream) -> std::io::Result<()> {
+//! match probe(&mut port, Duration::from_secs(2)).await? {
+//!     Probe::Ash { reset_code, .. } => println!("NCP in ASH mode, reset code {reset_code:?}"),
+//!     Probe::Bootloader { version } => println!("NCP in bootloader mode: {version:?}"),
+//!     Probe::Unknown => println!("No NCP found"),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::time::Duration;
+
+use log::{debug, trace};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::{Instant, timeout_at};
+
+use crate::bootloader::{PROMPT, rst, rst_ack};
+use crate::code::Code;
+use crate::deframer::Deframer;
+use crate::hex_slice::HexSlice;
+use crate::stats::Counters;
+
+/// Prefix of the bootloader's banner line that precedes its version.
+const BANNER: &str = "Gecko Bootloader";
+
+/// Size of the chunks read from the transport.
+const CHUNK_SIZE: usize = 64;
+
+/// Result of a [`probe`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Probe {
+    /// The NCP answered the `RST` frame with an `ASHv2` `RST_ACK` frame.
+    Ash {
+        /// The ASH version reported by the NCP.
+        version: u8,
+        /// The reset code reported by the NCP, or the raw byte if it is unknown.
+        reset_code: Result<Code, u8>,
+    },
+
+    /// The NCP presented the Gecko bootloader menu.
+    Bootloader {
+        /// The version from the bootloader's banner, e.g. `v2.4.1`, if it was received.
+        version: Option<String>,
+    },
+
+    /// The transport did not respond with anything recognizable.
+    Unknown,
+}
+
+impl Display for Probe {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ash {
+                version,
+                reset_code: Ok(code),
+            } => write!(f, "ASHv{version} ({code})"),
+            Self::Ash {
+                version,
+                reset_code: Err(code),
+            } => write!(f, "ASHv{version} (unknown reset code {code:#04X})"),
+            Self::Bootloader {
+                version: Some(version),
+            } => write!(f, "Gecko bootloader {version}"),
+            Self::Bootloader { version: None } => write!(f, "Gecko bootloader"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Probe the protocol spoken on `stream`.
+///
+/// Each of the two phases, waiting for the `RST_ACK` frame and waiting for the bootloader
+/// prompt, is limited to `timeout`.
+///
+/// # Errors
+///
+/// Returns an error if reading from or writing to the transport fails.
+pub async fn probe<T>(stream: &mut T, timeout: Duration) -> io::Result<Probe>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut received = Vec::new();
+    stream.write_all(&rst()).await?;
+
+    if let Some(probe) = listen(stream, timeout, &mut received).await? {
+        return Ok(probe);
+    }
+
+    debug!("No RST ACK frame received, requesting bootloader menu.");
+    stream.write_all(b"\r\n").await?;
+
+    if let Some(probe) = listen(stream, timeout, &mut received).await? {
+        return Ok(probe);
+    }
+
+    debug!("Unrecognized response: {:#04X}", HexSlice::new(&received));
+    Ok(Probe::Unknown)
+}
+
+/// Read from `stream` until a response is recognized, the stream ends or `timeout` elapsed.
+async fn listen<T>(
+    stream: &mut T,
+    timeout: Duration,
+    received: &mut Vec<u8>,
+) -> io::Result<Option<Probe>>
+where
+    T: AsyncRead + Unpin,
+{
+    let deadline = Instant::now() + timeout;
+    let counters = Counters::unpublished();
+    let mut deframer = Deframer::new();
+    let mut chunk = [0; CHUNK_SIZE];
+
+    while let Ok(result) = timeout_at(deadline, stream.read(&mut chunk)).await {
+        let len = result?;
+
+        if len == 0 {
+            debug!("Transport closed while probing.");
+            return Ok(None);
+        }
+
+        trace!("Received: {:#04X}", HexSlice::new(&chunk[..len]));
+
+        for &byte in &chunk[..len] {
+            if deframer.push(byte, &counters)
+                && let Some(rst_ack) = rst_ack(deframer.frame())
+                && rst_ack.is_ash_v2()
+            {
+                return Ok(Some(Probe::Ash {
+                    version: rst_ack.version(),
+                    reset_code: rst_ack.code(),
+                }));
+            }
+        }
+
+        received.extend_from_slice(&chunk[..len]);
+
+        if received.trim_ascii_end().ends_with(PROMPT) {
+            return Ok(Some(Probe::Bootloader {
+                version: bootloader_version(received),
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Extract the version from the bootloader's banner.
+fn bootloader_version(received: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(received)
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(BANNER))
+        .map(|version| version.trim().to_owned())
+        .filter(|version| !version.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex, join};
+    use tokio::runtime::{Builder, Runtime};
+
+    use super::{Probe, probe};
+    use crate::code::Code;
+    use crate::virtual_ncp;
+
+    const BUFFER_SIZE: usize = 1024;
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    fn runtime() -> Runtime {
+        Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .expect("runtime should build")
+    }
+
+    #[test]
+    fn probe_detects_ash() {
+        runtime().block_on(async {
+            let (_ncp, transport, futures) = virtual_ncp::start(Code::PowerOn);
+            let mut host = join(transport.reader, transport.writer);
+            tokio::spawn(futures.transmitter);
+            tokio::spawn(futures.receiver);
+
+            assert_eq!(
+                probe(&mut host, TIMEOUT)
+                    .await
+                    .expect("probe should succeed"),
+                Probe::Ash {
+                    version: 2,
+                    reset_code: Ok(Code::PowerOn)
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn probe_detects_bootloader() {
+        runtime().block_on(async {
+            let (mut host, mut ncp) = duplex(BUFFER_SIZE);
+            tokio::spawn(async move {
+                let mut byte = [0];
+
+                while ncp.read_exact(&mut byte).await.is_ok() {
+                    if byte == *b"\n" {
+                        ncp.write_all(
+                            b"\r\nGecko Bootloader v2.4.1\r\n1. upload gbl\r\n2. run\r\n3. ebl info\r\nBL > ",
+                        )
+                        .await
+                        .expect("menu should be sent");
+                    }
+                }
+            });
+
+            assert_eq!(
+                probe(&mut host, TIMEOUT).await.expect("probe should succeed"),
+                Probe::Bootloader {
+                    version: Some("v2.4.1".into())
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn probe_reports_silent_transport_as_unknown() {
+        runtime().block_on(async {
+            let (mut host, _ncp) = duplex(BUFFER_SIZE);
+
+            assert_eq!(
+                probe(&mut host, TIMEOUT)
+                    .await
+                    .expect("probe should succeed"),
+                Probe::Unknown
+            );
+        });
+    }
+}
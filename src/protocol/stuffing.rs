@@ -1,7 +1,5 @@
 //! Implementation of byte stuffing and un-stuffing.
 
-use std::io::{Error, ErrorKind, Result};
-
 use crate::protocol::ControlByte;
 use crate::protocol::control_byte::ESCAPE;
 
@@ -21,8 +19,8 @@ pub trait Stuff {
     ///
     /// # Errors
     ///
-    /// Returns an error if the escape byte could not be inserted, i.e. on potential buffer overflows.
-    fn stuff(&mut self) -> Result<()>;
+    /// Returns the reserved byte that could not be escaped, i.e. on potential buffer overflows.
+    fn stuff(&mut self) -> Result<(), u8>;
 }
 
 /// Un-stuff bytes.
@@ -32,17 +30,15 @@ pub trait Unstuff {
 }
 
 impl<const SIZE: usize> Stuff for heapless::Vec<u8, SIZE> {
-    fn stuff(&mut self) -> Result<()> {
+    fn stuff(&mut self) -> Result<(), u8> {
         let mut index: usize = 0;
 
         while index < self.len() {
-            let byte = &mut self[index];
+            let byte = self[index];
 
-            if RESERVED_BYTES.contains(byte) {
-                *byte ^= COMPLEMENT_BIT;
-                self.insert(index, ESCAPE).map_err(|_| {
-                    Error::new(ErrorKind::OutOfMemory, "Could not insert escape byte.")
-                })?;
+            if RESERVED_BYTES.contains(&byte) {
+                self.insert(index, ESCAPE).map_err(|_| byte)?;
+                self[index + 1] ^= COMPLEMENT_BIT;
                 index += 2;
             } else {
                 index += 1;
@@ -53,23 +49,36 @@ impl<const SIZE: usize> Stuff for heapless::Vec<u8, SIZE> {
     }
 }
 
+impl<const SIZE: usize> Unstuff for heapless::Vec<u8, SIZE> {
+    fn unstuff(&mut self) {
+        self.retain_mut(unstuffer());
+    }
+}
+
+#[cfg(any(feature = "std", test))]
 impl Unstuff for Vec<u8> {
     fn unstuff(&mut self) {
-        let mut escape_next = false;
+        self.retain_mut(unstuffer());
+    }
+}
 
-        self.retain_mut(|byte| {
-            if *byte == ESCAPE {
-                escape_next = true;
-                return false;
-            }
+/// Returns a stateful predicate for `retain_mut()` that removes escape bytes and restores the
+/// escaped bytes following them.
+fn unstuffer() -> impl FnMut(&mut u8) -> bool {
+    let mut escape_next = false;
 
-            if escape_next {
-                *byte ^= COMPLEMENT_BIT;
-                escape_next = false;
-            }
+    move |byte| {
+        if *byte == ESCAPE {
+            escape_next = true;
+            return false;
+        }
 
-            true
-        });
+        if escape_next {
+            *byte ^= COMPLEMENT_BIT;
+            escape_next = false;
+        }
+
+        true
     }
 }
 
@@ -100,6 +109,24 @@ mod tests {
         assert_eq!(stuffed.as_slice(), unstuffed.as_slice());
     }
 
+    #[test]
+    fn test_stuffing_overflow() {
+        let mut unstuffed: heapless::Vec<u8, 2> = [0x00, 0x7E].into_iter().collect();
+        assert_eq!(unstuffed.stuff(), Err(0x7E));
+    }
+
+    #[test]
+    fn test_unstuffing_heapless() {
+        let mut stuffed: heapless::Vec<u8, 12> = [
+            0x7D, 0x5E, 0x7D, 0x31, 0x7D, 0x33, 0x7D, 0x38, 0x7D, 0x3A, 0x7D, 0x5D,
+        ]
+        .into_iter()
+        .collect();
+        let unstuffed = [0x7E, 0x11, 0x13, 0x18, 0x1A, 0x7D];
+        stuffed.unstuff();
+        assert_eq!(stuffed.as_slice(), unstuffed.as_slice());
+    }
+
     #[test]
     fn test_unstuffing_unchanged() {
         let payload: Vec<u8> = vec![
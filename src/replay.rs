@@ -0,0 +1,550 @@
+//! Offline replay of recorded `ASHv2` byte streams.
+//!
+//! [`Replay`] feeds bytes received from an NCP, e.g. read from a capture file or parsed from a
+//! hex log with [`parse_hex`], through the same deframing and frame parsing as the actor's
+//! receiver. It produces a timeline of [`Entry`] values with the decoded frames, their CRC
+//! results, sequence anomalies and unmasked payloads, without requiring a live NCP.
+//!
+//! ```
+//! use ashv2::frame::Frame;
+//! use ashv2::replay::{CrcCheck, parse_hex};
+//!
+//! let bytes = parse_hex("C1 02 02 9B 7B 7E  # RST_ACK").expect("log should be valid hex");
+//! let timeline = ashv2::replay::replay(&bytes);
+//!
+//! assert!(matches!(timeline[0].frame, Ok(Frame::RstAck(_))));
+//! assert_eq!(timeline[0].crc, Some(CrcCheck::Valid(0x9B7B)));
+//! ```
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::ops::BitAnd;
+
+use crate::SEQ_MASK;
+use crate::deframer::Deframer;
+use crate::frame::{DecodeError, Frame};
+use crate::hex_slice::HexSlice;
+use crate::protocol::Mask;
+use crate::stats::{Counters, Stats};
+use crate::types::Payload;
+use crate::validate::Validate;
+
+/// Replays a recorded byte stream.
+#[derive(Debug)]
+pub struct Replay {
+    deframer: Deframer,
+    counters: Counters,
+    offset: usize,
+    last_received_frame_num: Option<u8>,
+}
+
+impl Replay {
+    /// Create a new replay at the start of a byte stream.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process the next byte of the stream.
+    ///
+    /// Returns an [`Entry`] if the byte terminated a frame.
+    pub fn push(&mut self, byte: u8) -> Option<Entry> {
+        let offset = self.offset;
+        self.offset += 1;
+        self.counters.bytes_received.increment();
+
+        if !self.deframer.push(byte, &self.counters) {
+            return None;
+        }
+
+        let frame = Frame::try_from(self.deframer.frame());
+        let raw = self.deframer.raw().to_vec();
+
+        match frame {
+            Ok(frame) => Some(self.inspect(offset, raw, frame)),
+            Err(error) => {
+                self.counters.decode_errors.increment();
+                Some(Entry {
+                    offset,
+                    raw,
+                    frame: Err(error),
+                    crc: None,
+                    anomaly: None,
+                    payload: None,
+                })
+            }
+        }
+    }
+
+    /// Process a chunk of the stream and return the entries of all frames terminated in it.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Entry> {
+        bytes.iter().filter_map(|&byte| self.push(byte)).collect()
+    }
+
+    /// Return the link statistics accumulated over the replayed bytes.
+    ///
+    /// Only counters of the receiving direction are populated.
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        self.counters.snapshot()
+    }
+
+    /// Return the frame bytes received after the last terminated frame.
+    ///
+    /// A non-empty result after replaying a whole recording indicates a truncated frame.
+    #[must_use]
+    pub fn pending(&self) -> &[u8] {
+        self.deframer.pending()
+    }
+
+    fn inspect(&mut self, offset: usize, raw: Vec<u8>, frame: Frame) -> Entry {
+        let crc = match frame.calculate_crc() {
+            calculated if calculated == frame.crc() => CrcCheck::Valid(calculated),
+            calculated => CrcCheck::Invalid {
+                received: frame.crc(),
+                calculated,
+            },
+        };
+        let mut anomaly = None;
+        let mut payload = None;
+
+        if crc.is_valid() {
+            self.count(&frame);
+
+            match &frame {
+                Frame::Data(data) => {
+                    let expected = self.ack_number();
+
+                    if data.frame_num() == expected {
+                        self.last_received_frame_num.replace(data.frame_num());
+                    } else if data.is_retransmission() {
+                        anomaly.replace(Anomaly::Retransmission {
+                            frame_num: data.frame_num(),
+                        });
+                    } else {
+                        self.counters.out_of_sequence_frames.increment();
+                        anomaly.replace(Anomaly::OutOfSequence {
+                            expected,
+                            received: data.frame_num(),
+                        });
+                    }
+
+                    let mut unmasked = data.clone().into_payload();
+                    unmasked.mask();
+                    payload.replace(unmasked);
+                }
+                Frame::Rst(_) | Frame::RstAck(_) => {
+                    self.last_received_frame_num.take();
+                }
+                Frame::Ack(_) | Frame::Error(_) | Frame::Nak(_) => {}
+            }
+        } else {
+            self.count_crc_failure(&frame);
+        }
+
+        Entry {
+            offset,
+            raw,
+            frame: Ok(frame),
+            crc: Some(crc),
+            anomaly,
+            payload,
+        }
+    }
+
+    /// Returns the ACK number the host would send, i.e. the next expected frame number.
+    fn ack_number(&self) -> u8 {
+        self.last_received_frame_num
+            .map_or(0x00, |frame_num| frame_num.wrapping_add(1))
+            .bitand(SEQ_MASK)
+    }
+
+    fn count(&self, frame: &Frame) {
+        match frame {
+            Frame::Ack(_) => self.counters.acks_received.increment(),
+            Frame::Data(_) => self.counters.data_frames_received.increment(),
+            Frame::Error(error) => self.counters.count_error(error.code()),
+            Frame::Nak(_) => self.counters.naks_received.increment(),
+            Frame::Rst(_) => {}
+            Frame::RstAck(rst_ack) => self.counters.count_reset(rst_ack.code()),
+        }
+    }
+
+    fn count_crc_failure(&self, frame: &Frame) {
+        let crc_failures = &self.counters.crc_failures;
+
+        match frame {
+            Frame::Ack(_) => crc_failures.ack.increment(),
+            Frame::Data(_) => crc_failures.data.increment(),
+            Frame::Error(_) => crc_failures.error.increment(),
+            Frame::Nak(_) => crc_failures.nak.increment(),
+            Frame::Rst(_) => crc_failures.rst.increment(),
+            Frame::RstAck(_) => crc_failures.rst_ack.increment(),
+        }
+    }
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Self {
+            deframer: Deframer::default(),
+            counters: Counters::unpublished(),
+            offset: 0,
+            last_received_frame_num: None,
+        }
+    }
+}
+
+/// A frame in the replayed timeline.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Entry {
+    /// Offset of the frame's terminating `FLAG` byte in the replayed stream.
+    pub offset: usize,
+
+    /// The stuffed bytes of the frame, including the terminating `FLAG` byte.
+    pub raw: Vec<u8>,
+
+    /// The decoded frame or the reason why it could not be decoded.
+    pub frame: Result<Frame, DecodeError>,
+
+    /// The result of the CRC check, if the frame could be decoded.
+    pub crc: Option<CrcCheck>,
+
+    /// A sequence anomaly of a `DATA` frame with a valid CRC.
+    pub anomaly: Option<Anomaly>,
+
+    /// The unmasked payload of a `DATA` frame with a valid CRC.
+    pub payload: Option<Payload>,
+}
+
+/// Formats the entry as a single timeline line.
+///
+/// Decoded frames are shown in their [`Display`] form, e.g. `DATA(2, 5, 0)`, followed by CRC
+/// failures, sequence anomalies and the unmasked payload, if any.
+impl Display for Entry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>8}  ", self.offset)?;
+
+        match &self.frame {
+            Ok(frame) => write!(f, "{frame}")?,
+            Err(error) => write!(f, "{:#04X} {error}", HexSlice::new(&self.raw))?,
+        }
+
+        if let Some(crc @ CrcCheck::Invalid { .. }) = self.crc {
+            write!(f, "  {crc}")?;
+        }
+
+        if let Some(anomaly) = self.anomaly {
+            write!(f, "  {anomaly}")?;
+        }
+
+        if let Some(payload) = &self.payload {
+            write!(f, "  payload: {:#04X}", HexSlice::new(payload))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Result of a frame's CRC check.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CrcCheck {
+    /// The received CRC matches the frame's content.
+    Valid(u16),
+
+    /// The received CRC does not match the frame's content.
+    Invalid {
+        /// The CRC received with the frame.
+        received: u16,
+
+        /// The CRC calculated over the frame's content.
+        calculated: u16,
+    },
+}
+
+impl CrcCheck {
+    /// Returns `true` if the CRC is valid.
+    #[must_use]
+    pub const fn is_valid(self) -> bool {
+        matches!(self, Self::Valid(_))
+    }
+}
+
+impl Display for CrcCheck {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Valid(crc) => write!(f, "valid CRC {crc:#06X}"),
+            Self::Invalid {
+                received,
+                calculated,
+            } => write!(
+                f,
+                "invalid CRC {received:#06X}, calculated {calculated:#06X}"
+            ),
+        }
+    }
+}
+
+/// Sequence anomalies of received `DATA` frames.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Anomaly {
+    /// A retransmitted frame that does not carry the expected frame number.
+    Retransmission {
+        /// The frame number of the retransmitted frame.
+        frame_num: u8,
+    },
+
+    /// A frame that does not carry the expected frame number and is not a retransmission.
+    ///
+    /// The host answers such frames with a `NAK`.
+    OutOfSequence {
+        /// The expected frame number.
+        expected: u8,
+
+        /// The received frame number.
+        received: u8,
+    },
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Retransmission { frame_num } => write!(f, "retransmission of frame {frame_num}"),
+            Self::OutOfSequence { expected, received } => {
+                write!(
+                    f,
+                    "out of sequence: expected frame {expected}, got {received}"
+                )
+            }
+        }
+    }
+}
+
+/// Replay a recorded byte stream and return its timeline.
+#[must_use]
+pub fn replay(bytes: &[u8]) -> Vec<Entry> {
+    Replay::new().feed(bytes)
+}
+
+/// Parse a hex log into bytes.
+///
+/// Bytes are separated by whitespace, commas or brackets and may carry a `0x` prefix, so both
+/// plain hex dumps and the crate's `{:#04X}` log output are accepted. Consecutive hex digit
+/// pairs without separators are split into bytes. Everything following a `#` on a line is
+/// ignored.
+///
+/// # Errors
+///
+/// Returns a [`ParseHexError`] on the first token that is not a valid hex byte sequence.
+pub fn parse_hex(log: &str) -> Result<Vec<u8>, ParseHexError> {
+    let mut bytes = Vec::new();
+
+    for (index, line) in log.lines().enumerate() {
+        let line = line.split_once('#').map_or(line, |(line, _)| line);
+
+        for token in line
+            .split(|char: char| char.is_whitespace() || matches!(char, ',' | '[' | ']'))
+            .filter(|token| !token.is_empty())
+        {
+            let error = || ParseHexError {
+                line: index + 1,
+                token: token.to_owned(),
+            };
+            let digits = token
+                .strip_prefix("0x")
+                .or_else(|| token.strip_prefix("0X"))
+                .unwrap_or(token);
+
+            if digits.is_empty() || digits.len() % 2 != 0 {
+                return Err(error());
+            }
+
+            for pair in digits.as_bytes().chunks_exact(2) {
+                let pair = str::from_utf8(pair).map_err(|_| error())?;
+                bytes.push(u8::from_str_radix(pair, 16).map_err(|_| error())?);
+            }
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Error returned by [`parse_hex`] for tokens that are not hex bytes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseHexError {
+    line: usize,
+    token: String,
+}
+
+impl ParseHexError {
+    /// Returns the one-based line number of the invalid token.
+    #[must_use]
+    pub const fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the invalid token.
+    #[must_use]
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+}
+
+impl Display for ParseHexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid hex byte `{}` on line {}.",
+            self.token, self.line
+        )
+    }
+}
+
+impl Error for ParseHexError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anomaly, CrcCheck, Entry, ParseHexError, Replay, parse_hex, replay};
+    use crate::frame::{Data, DecodeError, Frame};
+    use crate::protocol::Stuff;
+    use crate::types::{Payload, RawFrame};
+
+    const RST_ACK: [u8; 6] = [0xC1, 0x02, 0x02, 0x9B, 0x7B, 0x7E];
+    const BAD_ACK: [u8; 4] = [0x81, 0x60, 0x58, 0x7E];
+
+    fn data(frame_num: u8, retransmit: bool, payload: &[u8]) -> Vec<u8> {
+        let mut data = Data::new(
+            frame_num,
+            0,
+            Payload::from_slice(payload).expect("payload should fit"),
+        );
+        data.set_is_retransmission(retransmit);
+        let mut bytes: RawFrame = data.iter().collect();
+        bytes.stuff().expect("frame should fit");
+        let mut bytes = bytes.to_vec();
+        bytes.push(0x7E);
+        bytes
+    }
+
+    #[test]
+    fn replay_decodes_timeline() {
+        let mut stream = RST_ACK.to_vec();
+        stream.extend(data(0, false, &[0x00, 0x80, 0x00, 0x02, 0x02, 0x11, 0x30]));
+        stream.extend(BAD_ACK);
+        stream.extend([0xFE, 0x00, 0x00, 0x7E]);
+
+        let timeline = replay(&stream);
+
+        assert_eq!(timeline.len(), 4);
+        assert_eq!(timeline[0].offset, 5);
+        assert_eq!(timeline[0].crc, Some(CrcCheck::Valid(0x9B7B)));
+        assert_eq!(timeline[1].anomaly, None);
+        assert_eq!(
+            timeline[1].payload.as_deref(),
+            Some([0x00, 0x80, 0x00, 0x02, 0x02, 0x11, 0x30].as_slice())
+        );
+        assert_eq!(
+            timeline[2].crc,
+            Some(CrcCheck::Invalid {
+                received: 0x6058,
+                calculated: 0x6059,
+            })
+        );
+        assert_eq!(
+            timeline[3],
+            Entry {
+                offset: stream.len() - 1,
+                raw: vec![0xFE, 0x00, 0x00, 0x7E],
+                frame: Err(DecodeError::UnknownHeader(0xFE)),
+                crc: None,
+                anomaly: None,
+                payload: None,
+            }
+        );
+    }
+
+    #[test]
+    fn entry_displays_timeline_line() {
+        let mut stream = data(2, false, &[0x01, 0x02, 0x03]);
+        stream.extend(BAD_ACK);
+        stream.extend([0xFE, 0x00, 0x00, 0x7E]);
+
+        let lines: Vec<_> = replay(&stream).iter().map(ToString::to_string).collect();
+
+        assert_eq!(
+            lines,
+            [
+                "       6  DATA(2, 0, 0)  out of sequence: expected frame 0, got 2  payload: [0x01, 0x02, 0x03]",
+                "      10  ACK(1)+  invalid CRC 0x6058, calculated 0x6059",
+                "      14  [0xFE, 0x00, 0x00, 0x7E] Unknown frame header: 0xFE",
+            ]
+        );
+    }
+
+    #[test]
+    fn replay_detects_sequence_anomalies() {
+        let mut replay = Replay::new();
+        replay.feed(&RST_ACK);
+
+        let timeline = replay.feed(
+            &[
+                data(0, false, &[0x01, 0x02, 0x03]),
+                data(0, true, &[0x01, 0x02, 0x03]),
+                data(2, false, &[0x04, 0x05, 0x06]),
+                data(1, false, &[0x07, 0x08, 0x09]),
+            ]
+            .concat(),
+        );
+        let anomalies: Vec<_> = timeline.iter().map(|entry| entry.anomaly).collect();
+
+        assert_eq!(
+            anomalies,
+            [
+                None,
+                Some(Anomaly::Retransmission { frame_num: 0 }),
+                Some(Anomaly::OutOfSequence {
+                    expected: 1,
+                    received: 2,
+                }),
+                None,
+            ]
+        );
+        assert!(matches!(timeline[3].frame, Ok(Frame::Data(_))));
+
+        let stats = replay.stats();
+        assert_eq!(stats.data_frames_received, 4);
+        assert_eq!(stats.out_of_sequence_frames, 1);
+        assert_eq!(stats.resets.values().sum::<u64>(), 1);
+    }
+
+    #[test]
+    fn replay_reports_pending_bytes() {
+        let mut replay = Replay::new();
+        replay.feed(&RST_ACK);
+        assert!(replay.pending().is_empty());
+
+        replay.feed(&[0x81, 0x60]);
+        assert_eq!(replay.pending(), [0x81, 0x60]);
+    }
+
+    #[test]
+    fn parse_hex_accepts_common_formats() {
+        let log = "# host <- ncp\n[0xC1, 0x02, 0x02]\n9B 7b\n7E # end of RST_ACK\n";
+        assert_eq!(parse_hex(log), Ok(RST_ACK.to_vec()));
+        assert_eq!(parse_hex("c102029b7b7e"), Ok(RST_ACK.to_vec()));
+    }
+
+    #[test]
+    fn parse_hex_rejects_invalid_tokens() {
+        let error = parse_hex("7E\n81 6G").expect_err("6G should be rejected");
+        assert_eq!(
+            error,
+            ParseHexError {
+                line: 2,
+                token: "6G".to_owned(),
+            }
+        );
+        assert!(parse_hex("0x").is_err());
+        assert!(parse_hex("123").is_err());
+    }
+}
@@ -0,0 +1,265 @@
+//! Serial port transport with line settings suitable for `ASHv2`.
+//!
+//! This module is available with the `serial` crate feature. [`open`] opens a TTY through
+//! [`tokio_serial`] as 8N1 with the configured baud rate and flow control, and [`start`]
+//! additionally splits the port and starts the host actor on it. [`detect_baud_rate`] finds the
+//! baud rate of an NCP with unknown firmware configuration.
+//!
+//! The [`Default`] [`Settings`] match the usual NCP firmware configuration of 115200 baud with
+//! RTS/CTS flow control. NCPs configured for software flow control commonly use 57600 baud with
+//! XON/XOFF, which the receiver already ignores in the byte stream.
+//!
+//! ```no_run
+//! use ashv2::serial::{self, Settings};
+//! use tokio::sync::mpsc::channel;
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let (response_tx, response_rx) = channel(64);
+//! let (handle, futures) = serial::start("/dev/ttyUSB0", Settings::default(), response_tx)?;
+//! tokio::spawn(futures.transmitter);
+//! tokio::spawn(futures.receiver);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io;
+use std::time::Duration;
+
+use log::debug;
+use tokio::sync::mpsc::Sender;
+pub use tokio_serial::FlowControl;
+use tokio_serial::{DataBits, Parity, SerialPortBuilderExt, SerialStream, StopBits};
+
+use crate::probe::{Probe, probe};
+use crate::types::Payload;
+use crate::{Futures, Handle};
+
+/// The default baud rate of NCPs using hardware flow control.
+pub const DEFAULT_BAUD_RATE: u32 = 115_200;
+
+/// Baud rates of common NCP firmware, in the order tried by [`detect_baud_rate`].
+pub const CANDIDATE_BAUD_RATES: [u32; 3] = [DEFAULT_BAUD_RATE, 57_600, 460_800];
+
+/// Line settings of a serial port.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Settings {
+    /// The baud rate.
+    pub baud_rate: u32,
+
+    /// The flow control, either RTS/CTS ([`FlowControl::Hardware`]) or XON/XOFF
+    /// ([`FlowControl::Software`]).
+    pub flow_control: FlowControl,
+
+    /// Whether to lock the TTY against being opened by other processes.
+    ///
+    /// This setting only takes effect on Unix platforms.
+    pub exclusive: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            baud_rate: DEFAULT_BAUD_RATE,
+            flow_control: FlowControl::Hardware,
+            exclusive: true,
+        }
+    }
+}
+
+/// Open the serial port at `path` with the given settings as 8N1.
+///
+/// # Errors
+///
+/// Returns an error if the port could not be opened or configured.
+pub fn open(path: &str, settings: Settings) -> io::Result<SerialStream> {
+    #[cfg_attr(not(unix), expect(unused_mut))]
+    let mut port = tokio_serial::new(path, settings.baud_rate)
+        .data_bits(DataBits::Eight)
+        .parity(Parity::None)
+        .stop_bits(StopBits::One)
+        .flow_control(settings.flow_control)
+        .open_native_async()?;
+
+    #[cfg(unix)]
+    port.set_exclusive(settings.exclusive)?;
+
+    Ok(port)
+}
+
+/// Open the serial port at `path`, split it and start the host actor on it.
+///
+/// See [`crate::start`] for how to drive the returned futures.
+///
+/// # Errors
+///
+/// Returns an error if the port could not be opened or configured.
+pub fn start(
+    path: &str,
+    settings: Settings,
+    response: Sender<Payload>,
+) -> io::Result<(
+    Handle,
+    Futures<impl Future<Output = ()> + Send + 'static, impl Future<Output = ()> + Send + 'static>,
+)> {
+    let (reader, writer) = tokio::io::split(open(path, settings)?);
+    Ok(crate::start(reader, writer, response))
+}
+
+/// Detect the baud rate of the NCP at `path`.
+///
+/// The port is opened with each of the `candidates` in turn, see [`CANDIDATE_BAUD_RATES`], and
+/// [probed](crate::probe) with the given `timeout`. The remaining `settings` are used as given.
+///
+/// Returns the settings with the first baud rate at which the NCP answered with an `ASHv2`
+/// `RST_ACK` frame, or `None` if it did not answer at any of the candidates.
+///
+/// # Errors
+///
+/// Returns an error if the port could not be opened, configured, read or written.
+pub async fn detect_baud_rate(
+    path: &str,
+    settings: Settings,
+    candidates: &[u32],
+    timeout: Duration,
+) -> io::Result<Option<Settings>> {
+    for &baud_rate in candidates {
+        let settings = Settings {
+            baud_rate,
+            ..settings
+        };
+        let mut port = open(path, settings)?;
+        let result = probe(&mut port, timeout).await?;
+        debug!("Probed {path} at {baud_rate} baud: {result}");
+
+        if let Probe::Ash { .. } = result {
+            return Ok(Some(settings));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::runtime::Builder;
+    use tokio::sync::mpsc::channel;
+    use tokio_serial::{SerialPort, SerialStream};
+
+    use super::{CANDIDATE_BAUD_RATES, Settings, detect_baud_rate, open, start};
+    use crate::code::Code;
+    use crate::frame::RstAck;
+    use crate::ncp;
+    use crate::protocol::Stuff;
+    use crate::types::RawFrame;
+
+    #[test]
+    fn open_applies_settings() {
+        Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (_master, slave) = SerialStream::pair().expect("pty pair should open");
+                let path = slave.name().expect("pty should have a name");
+                let settings = Settings {
+                    baud_rate: 57_600,
+                    ..Settings::default()
+                };
+
+                let port = open(&path, settings).expect("pty should open");
+
+                assert_eq!(port.baud_rate().expect("baud rate should be set"), 57_600);
+                assert!(port.exclusive());
+            });
+    }
+
+    #[test]
+    fn start_talks_to_ncp_over_pty() {
+        Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (master, slave) = SerialStream::pair().expect("pty pair should open");
+                let path = slave.name().expect("pty should have a name");
+                let (ncp_tx, mut ncp_rx) = channel(8);
+                let (ncp_reader, ncp_writer) = tokio::io::split(master);
+                let (_ncp, ncp_futures) = ncp::start(ncp_reader, ncp_writer, ncp_tx, Code::PowerOn);
+                let (response_tx, _response_rx) = channel(8);
+                let (handle, futures) =
+                    start(&path, Settings::default(), response_tx).expect("pty should open");
+                drop(slave);
+
+                let ncp_transmitter = tokio::spawn(ncp_futures.transmitter);
+                let ncp_receiver = tokio::spawn(ncp_futures.receiver);
+                let transmitter = tokio::spawn(futures.transmitter);
+                let receiver = tokio::spawn(futures.receiver);
+
+                let request = [0x00, 0x00, 0x00, 0x02].into_iter().collect();
+                handle.send(request).await.expect("request should be sent");
+                let payload = ncp_rx.recv().await.expect("NCP should receive the request");
+                assert_eq!(payload.as_slice(), [0x00, 0x00, 0x00, 0x02]);
+
+                transmitter.abort();
+                receiver.abort();
+                ncp_transmitter.abort();
+                ncp_receiver.abort();
+            });
+    }
+
+    #[test]
+    fn detect_baud_rate_finds_answering_rate() {
+        const NCP_BAUD_RATE: u32 = 460_800;
+
+        Builder::new_current_thread()
+            .enable_io()
+            .enable_time()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (mut master, slave) = SerialStream::pair().expect("pty pair should open");
+                let path = slave.name().expect("pty should have a name");
+                // Only answer RST frames received while the port is set to the NCP's baud rate.
+                tokio::spawn(async move {
+                    let mut rst_ack: RawFrame = RstAck::new(Code::PowerOn).into_iter().collect();
+                    rst_ack.stuff().expect("frame should fit");
+                    rst_ack.push(0x7E).expect("frame should fit");
+                    let mut byte = [0];
+
+                    while master.read_exact(&mut byte).await.is_ok() {
+                        if byte == [0x7E] && master.baud_rate().ok() == Some(NCP_BAUD_RATE) {
+                            master
+                                .write_all(&rst_ack)
+                                .await
+                                .expect("RST ACK should be sent");
+                        }
+                    }
+                });
+                let settings = Settings {
+                    exclusive: false,
+                    ..Settings::default()
+                };
+
+                let detected = detect_baud_rate(
+                    &path,
+                    settings,
+                    &CANDIDATE_BAUD_RATES,
+                    Duration::from_millis(100),
+                )
+                .await
+                .expect("ports should be probed");
+
+                assert_eq!(
+                    detected,
+                    Some(Settings {
+                        baud_rate: NCP_BAUD_RATE,
+                        ..settings
+                    })
+                );
+                drop(slave);
+            });
+    }
+}
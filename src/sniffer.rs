@@ -0,0 +1,329 @@
+//! Passive monitoring of an existing `ASHv2` link.
+//!
+//! A [`Sniffer`] consumes the two directions of a link tapped from its TX and RX lines and never
+//! writes to either of them. The frames of each direction are decoded like in
+//! [`crate::replay`], so each [`Event`] carries the frame with its CRC result, sequence anomalies
+//! and unmasked payload. In addition, the sniffer tracks the frame numbers sent by both sides and
+//! reports acknowledgement numbers that acknowledge frames which were never sent.
+//!
+//! [`Monitor`] implements the decoding without I/O and can be fed with timestamped chunks of
+//! recorded bytes directly.
+
+use std::fmt::{Display, Formatter};
+use std::time::SystemTime;
+
+use log::{error, warn};
+use tokio::io::AsyncRead;
+use tokio::sync::mpsc::Sender;
+use tokio_stream::StreamExt;
+use tokio_util::io::ReaderStream;
+
+use crate::SEQ_MASK;
+use crate::frame::Frame;
+use crate::observer::Direction;
+use crate::replay::{CrcCheck, Entry, Replay};
+
+/// Passive monitor reading both directions of a link.
+#[derive(Debug)]
+pub struct Sniffer<H, N> {
+    host: H,
+    ncp: N,
+}
+
+impl<H, N> Sniffer<H, N>
+where
+    H: AsyncRead + Unpin,
+    N: AsyncRead + Unpin,
+{
+    /// Create a new sniffer.
+    ///
+    /// `host` provides the bytes sent from the host to the NCP and `ncp` the bytes sent from the
+    /// NCP to the host.
+    pub const fn new(host: H, ncp: N) -> Self {
+        Self { host, ncp }
+    }
+
+    /// Decode both directions of the link and send the resulting events to `events`.
+    ///
+    /// Returns once both byte streams have ended or the event receiver has been dropped.
+    pub async fn run(self, events: Sender<Event>) {
+        let host = ReaderStream::new(self.host).map(|chunk| (Direction::Sent, chunk));
+        let ncp = ReaderStream::new(self.ncp).map(|chunk| (Direction::Received, chunk));
+        let mut chunks = host.merge(ncp);
+        let mut monitor = Monitor::new();
+
+        while let Some((direction, chunk)) = chunks.next().await {
+            let bytes = match chunk {
+                Ok(bytes) => bytes,
+                Err(error) => {
+                    error!("Error reading {direction:?} bytes: {error}");
+                    continue;
+                }
+            };
+
+            for event in monitor.push(direction, SystemTime::now(), &bytes) {
+                if events.send(event).await.is_err() {
+                    warn!("Event receiver dropped, sniffer exiting.");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Decodes both directions of a link without performing I/O.
+///
+/// Unlike the actor, a monitor neither takes connection IDs nor publishes metrics.
+#[derive(Debug, Default)]
+pub struct Monitor {
+    host: Side,
+    ncp: Side,
+}
+
+impl Monitor {
+    /// Create a new monitor.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process a chunk of bytes of the given direction received at `timestamp`.
+    ///
+    /// [`Direction::Sent`] denotes bytes sent by the host and [`Direction::Received`] bytes
+    /// sent by the NCP. Returns the events of all frames terminated in the chunk.
+    pub fn push(
+        &mut self,
+        direction: Direction,
+        timestamp: SystemTime,
+        bytes: &[u8],
+    ) -> Vec<Event> {
+        let (sender, peer) = match direction {
+            Direction::Sent => (&mut self.host, &mut self.ncp),
+            Direction::Received => (&mut self.ncp, &mut self.host),
+        };
+
+        sender
+            .replay
+            .feed(bytes)
+            .into_iter()
+            .map(|entry| Event {
+                timestamp,
+                direction,
+                invalid_ack: track(sender, peer, &entry),
+                entry,
+            })
+            .collect()
+    }
+
+    /// Return the bytes of each direction received after its last terminated frame.
+    #[must_use]
+    pub fn pending(&self, direction: Direction) -> &[u8] {
+        match direction {
+            Direction::Sent => self.host.replay.pending(),
+            Direction::Received => self.ncp.replay.pending(),
+        }
+    }
+}
+
+/// A frame observed on the link.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Event {
+    /// The time at which the chunk terminating the frame was read.
+    pub timestamp: SystemTime,
+
+    /// The direction of the frame from the host's point of view.
+    pub direction: Direction,
+
+    /// The decoded frame.
+    pub entry: Entry,
+
+    /// An acknowledgement number acknowledging frames that the peer has not sent.
+    pub invalid_ack: Option<InvalidAck>,
+}
+
+/// An acknowledgement number outside the peer's window of unacknowledged frames.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct InvalidAck {
+    /// The received acknowledgement number.
+    pub ack_num: u8,
+
+    /// The acknowledgement number last sent to the peer.
+    pub acknowledged: u8,
+
+    /// The frame number of the peer's next `DATA` frame.
+    pub next_frame_num: u8,
+}
+
+impl Display for InvalidAck {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid ACK number {}: expected {} to {}",
+            self.ack_num, self.acknowledged, self.next_frame_num
+        )
+    }
+}
+
+/// State of frames sent by one side of the link.
+#[derive(Debug, Default)]
+struct Side {
+    replay: Replay,
+    next_frame_num: u8,
+    acknowledged: u8,
+}
+
+impl Side {
+    const fn reset(&mut self) {
+        self.next_frame_num = 0;
+        self.acknowledged = 0;
+    }
+
+    /// Check an acknowledgement number received from the peer against the sent frames.
+    const fn acknowledge(&mut self, ack_num: u8) -> Option<InvalidAck> {
+        let outstanding = self.next_frame_num.wrapping_sub(self.acknowledged) & SEQ_MASK;
+        let advance = ack_num.wrapping_sub(self.acknowledged) & SEQ_MASK;
+
+        if advance <= outstanding {
+            self.acknowledged = ack_num;
+            return None;
+        }
+
+        Some(InvalidAck {
+            ack_num,
+            acknowledged: self.acknowledged,
+            next_frame_num: self.next_frame_num,
+        })
+    }
+}
+
+/// Track the frame and acknowledgement numbers of a frame sent by `sender`.
+fn track(sender: &mut Side, peer: &mut Side, entry: &Entry) -> Option<InvalidAck> {
+    if !entry.crc.is_some_and(CrcCheck::is_valid) {
+        return None;
+    }
+
+    match &entry.frame {
+        Ok(Frame::Data(data)) => {
+            if entry.anomaly.is_none() {
+                sender.next_frame_num = data.frame_num().wrapping_add(1) & SEQ_MASK;
+            }
+
+            peer.acknowledge(data.ack_num())
+        }
+        Ok(Frame::Ack(ack)) => peer.acknowledge(ack.ack_num()),
+        Ok(Frame::Nak(nak)) => peer.acknowledge(nak.ack_num()),
+        Ok(Frame::Rst(_) | Frame::RstAck(_)) => {
+            sender.reset();
+            peer.reset();
+            None
+        }
+        Ok(Frame::Error(_)) | Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use tokio::runtime::Builder;
+    use tokio::sync::mpsc::channel;
+
+    use super::{Direction, InvalidAck, Monitor, Sniffer};
+    use crate::frame::{Ack, Data, Frame, Nak, RST};
+    use crate::protocol::Stuff;
+    use crate::replay::Anomaly;
+    use crate::types::{Payload, RawFrame};
+
+    const RST_ACK: [u8; 6] = [0xC1, 0x02, 0x02, 0x9B, 0x7B, 0x7E];
+
+    fn encode<T>(frame: T) -> Vec<u8>
+    where
+        T: IntoIterator<Item = u8>,
+    {
+        let mut bytes: RawFrame = frame.into_iter().collect();
+        bytes.stuff().expect("frame should fit");
+        let mut bytes = bytes.to_vec();
+        bytes.push(0x7E);
+        bytes
+    }
+
+    fn data(frame_num: u8, ack_num: u8, retransmit: bool) -> Vec<u8> {
+        let payload = Payload::from_slice(&[0x01, 0x02, 0x03]).expect("payload should fit");
+        let mut data = Data::new(frame_num, ack_num, payload);
+        data.set_is_retransmission(retransmit);
+        encode(&data)
+    }
+
+    #[test]
+    fn monitor_tracks_both_directions() {
+        let mut monitor = Monitor::new();
+        let now = SystemTime::now();
+
+        let events = monitor.push(Direction::Sent, now, &encode(RST));
+        assert!(matches!(events[0].entry.frame, Ok(Frame::Rst(_))));
+        let events = monitor.push(Direction::Received, now, &RST_ACK);
+        assert!(matches!(events[0].entry.frame, Ok(Frame::RstAck(_))));
+
+        let mut host = data(0, 0, false);
+        host.extend(data(1, 0, false));
+        assert_eq!(monitor.push(Direction::Sent, now, &host).len(), 2);
+
+        let mut ncp = encode(Ack::new(1, false));
+        ncp.extend(encode(Nak::new(1, false)));
+        ncp.extend(encode(Ack::new(4, false)));
+        let events = monitor.push(Direction::Received, now, &ncp);
+        let invalid: Vec<_> = events.iter().map(|event| event.invalid_ack).collect();
+        assert_eq!(
+            invalid,
+            [
+                None,
+                None,
+                Some(InvalidAck {
+                    ack_num: 4,
+                    acknowledged: 1,
+                    next_frame_num: 2,
+                }),
+            ]
+        );
+
+        let events = monitor.push(Direction::Sent, now, &data(1, 0, true));
+        assert_eq!(
+            events[0].entry.anomaly,
+            Some(Anomaly::Retransmission { frame_num: 1 })
+        );
+        assert_eq!(events[0].invalid_ack, None);
+        assert_eq!(events[0].direction, Direction::Sent);
+    }
+
+    #[test]
+    fn monitor_takes_no_connection_ids() {
+        let monitor = Monitor::new();
+        assert_eq!(monitor.host.replay.stats().connection_id, 0);
+        assert_eq!(monitor.ncp.replay.stats().connection_id, 0);
+    }
+
+    #[test]
+    fn sniffer_reads_both_streams() {
+        Builder::new_current_thread()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let host = encode(RST);
+                let ncp = RST_ACK.to_vec();
+                let (events_tx, mut events_rx) = channel(8);
+
+                Sniffer::new(host.as_slice(), ncp.as_slice())
+                    .run(events_tx)
+                    .await;
+
+                let mut directions = Vec::new();
+
+                while let Some(event) = events_rx.recv().await {
+                    directions.push(event.direction);
+                }
+
+                directions.sort_by_key(|direction| *direction == Direction::Received);
+                assert_eq!(directions, [Direction::Sent, Direction::Received]);
+            });
+    }
+}
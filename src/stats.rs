@@ -0,0 +1,131 @@
+//! Link statistics of the `ASHv2` actor.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+pub use self::counters::Counters;
+use crate::code::Code;
+
+mod counters;
+mod instruments;
+
+/// Snapshot of the link statistics of an `ASHv2` actor.
+///
+/// All counters are cumulative since the actor futures were created. Gauges reflect the state at
+/// the time of their last update.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    /// Process-wide unique ID of the connection.
+    ///
+    /// Statistics that do not belong to a connection, such as those of a
+    /// [`Replay`](crate::replay::Replay), carry ID `0`.
+    ///
+    /// With the `metrics` crate feature, the connection's metrics carry this ID in their
+    /// `connection` label.
+    pub connection_id: u64,
+
+    /// Number of `DATA` frames sent for the first time.
+    pub data_frames_sent: u64,
+
+    /// Number of valid `DATA` frames received, including retransmitted and out-of-sequence frames.
+    pub data_frames_received: u64,
+
+    /// Number of `DATA` frames retransmitted after a `NAK`.
+    pub retransmissions: u64,
+
+    /// Number of `ACK` frames sent.
+    pub acks_sent: u64,
+
+    /// Number of valid `ACK` frames received.
+    pub acks_received: u64,
+
+    /// Number of `NAK` frames sent.
+    pub naks_sent: u64,
+
+    /// Number of valid `NAK` frames received.
+    pub naks_received: u64,
+
+    /// Number of received frames with an invalid CRC by frame type.
+    pub crc_failures: CrcFailures,
+
+    /// Number of valid `DATA` frames received out of sequence.
+    pub out_of_sequence_frames: u64,
+
+    /// Number of received frames that could not be decoded.
+    pub decode_errors: u64,
+
+    /// Number of received `SUBSTITUTE` bytes.
+    pub substitutes: u64,
+
+    /// Number of received `CANCEL` bytes.
+    pub cancels: u64,
+
+    /// Number of `RST` frames sent.
+    pub rsts_sent: u64,
+
+    /// Number of valid `RST_ACK` frames received by reset code.
+    pub resets: BTreeMap<Code, u64>,
+
+    /// Number of valid `ERROR` frames received by error code.
+    pub errors: BTreeMap<Code, u64>,
+
+    /// Number of valid `RST_ACK` and `ERROR` frames received with an unknown code.
+    pub unknown_codes: u64,
+
+    /// Number of bytes written to the transport.
+    pub bytes_sent: u64,
+
+    /// Number of bytes read from the transport.
+    pub bytes_received: u64,
+
+    /// Number of bytes added by byte stuffing to the bytes written to the transport.
+    pub stuffing_overhead_sent: u64,
+
+    /// Number of bytes removed by byte unstuffing from the bytes read from the transport.
+    pub stuffing_overhead_received: u64,
+
+    /// Number of sent `DATA` frames that have been acknowledged.
+    pub acked_frames: u64,
+
+    /// Sum of the latencies between sending and acknowledgement of acknowledged `DATA` frames.
+    pub ack_latency_total: Duration,
+
+    /// Number of sent `DATA` frames currently awaiting acknowledgement.
+    pub window_occupancy: u64,
+
+    /// Number of messages queued for the transmitter when it last received a message.
+    pub message_queue_depth: u64,
+
+    /// Number of inbound payloads queued in the response channel after the last delivery.
+    pub response_queue_depth: u64,
+}
+
+/// Number of received frames with an invalid CRC by frame type.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct CrcFailures {
+    /// `ACK` frames with an invalid CRC.
+    pub ack: u64,
+
+    /// `DATA` frames with an invalid CRC.
+    pub data: u64,
+
+    /// `ERROR` frames with an invalid CRC.
+    pub error: u64,
+
+    /// `NAK` frames with an invalid CRC.
+    pub nak: u64,
+
+    /// `RST` frames with an invalid CRC.
+    pub rst: u64,
+
+    /// `RST_ACK` frames with an invalid CRC.
+    pub rst_ack: u64,
+}
+
+impl CrcFailures {
+    /// Return the total number of frames with an invalid CRC.
+    #[must_use]
+    pub const fn total(&self) -> u64 {
+        self.ack + self.data + self.error + self.nak + self.rst + self.rst_ack
+    }
+}
@@ -0,0 +1,278 @@
+//! Shared counters updated by the actor futures.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::{Mutex, PoisonError};
+
+use tokio::sync::watch;
+
+use super::instruments::{Counter, Gauge, Labels, Latency};
+use super::{CrcFailures, Stats};
+use crate::code::Code;
+
+/// Source of unique connection IDs.
+///
+/// ID `0` is reserved for unpublished counters, which do not belong to a connection.
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Counters of received frames with an invalid CRC by frame type.
+#[derive(Debug)]
+pub struct CrcFailureCounters {
+    pub ack: Counter,
+    pub data: Counter,
+    pub error: Counter,
+    pub nak: Counter,
+    pub rst: Counter,
+    pub rst_ack: Counter,
+}
+
+impl CrcFailureCounters {
+    fn new(labels: &Labels) -> Self {
+        const NAME: &str = "ashv2_crc_failures_total";
+
+        Self {
+            ack: Counter::new(NAME, &labels.with("frame_type", "ACK")),
+            data: Counter::new(NAME, &labels.with("frame_type", "DATA")),
+            error: Counter::new(NAME, &labels.with("frame_type", "ERROR")),
+            nak: Counter::new(NAME, &labels.with("frame_type", "NAK")),
+            rst: Counter::new(NAME, &labels.with("frame_type", "RST")),
+            rst_ack: Counter::new(NAME, &labels.with("frame_type", "RST_ACK")),
+        }
+    }
+
+    fn snapshot(&self) -> CrcFailures {
+        CrcFailures {
+            ack: self.ack.get(),
+            data: self.data.get(),
+            error: self.error.get(),
+            nak: self.nak.get(),
+            rst: self.rst.get(),
+            rst_ack: self.rst_ack.get(),
+        }
+    }
+}
+
+/// Link statistics shared between the actor futures and their handles.
+#[derive(Debug)]
+pub struct Counters {
+    connection_id: u64,
+    labels: Labels,
+    pub data_frames_sent: Counter,
+    pub data_frames_received: Counter,
+    pub retransmissions: Counter,
+    pub acks_sent: Counter,
+    pub acks_received: Counter,
+    pub naks_sent: Counter,
+    pub naks_received: Counter,
+    pub crc_failures: CrcFailureCounters,
+    pub out_of_sequence_frames: Counter,
+    pub decode_errors: Counter,
+    pub substitutes: Counter,
+    pub cancels: Counter,
+    pub rsts_sent: Counter,
+    pub bytes_sent: Counter,
+    pub bytes_received: Counter,
+    pub stuffing_overhead_sent: Counter,
+    pub stuffing_overhead_received: Counter,
+    pub ack_latency: Latency,
+    pub window_occupancy: Gauge,
+    pub message_queue_depth: Gauge,
+    pub response_queue_depth: Gauge,
+    codes: Mutex<Codes>,
+    connections: watch::Sender<u64>,
+}
+
+impl Counters {
+    /// Create counters for a new connection.
+    pub fn new() -> Self {
+        let connection_id = NEXT_CONNECTION_ID.fetch_add(1, Relaxed);
+        Self::with_labels(connection_id, Labels::new(connection_id))
+    }
+
+    /// Create counters that do not belong to a connection.
+    ///
+    /// They neither take a connection ID nor publish metrics, which suits decoding byte streams
+    /// outside of the actor.
+    pub fn unpublished() -> Self {
+        Self::with_labels(0, Labels::unpublished())
+    }
+
+    fn with_labels(connection_id: u64, labels: Labels) -> Self {
+        Self {
+            connection_id,
+            data_frames_sent: Counter::new("ashv2_data_frames_sent_total", &labels),
+            data_frames_received: Counter::new("ashv2_data_frames_received_total", &labels),
+            retransmissions: Counter::new("ashv2_retransmissions_total", &labels),
+            acks_sent: Counter::new("ashv2_acks_sent_total", &labels),
+            acks_received: Counter::new("ashv2_acks_received_total", &labels),
+            naks_sent: Counter::new("ashv2_naks_sent_total", &labels),
+            naks_received: Counter::new("ashv2_naks_received_total", &labels),
+            crc_failures: CrcFailureCounters::new(&labels),
+            out_of_sequence_frames: Counter::new("ashv2_out_of_sequence_frames_total", &labels),
+            decode_errors: Counter::new("ashv2_decode_errors_total", &labels),
+            substitutes: Counter::new("ashv2_substitutes_total", &labels),
+            cancels: Counter::new("ashv2_cancels_total", &labels),
+            rsts_sent: Counter::new("ashv2_rsts_sent_total", &labels),
+            bytes_sent: Counter::new("ashv2_bytes_sent_total", &labels),
+            bytes_received: Counter::new("ashv2_bytes_received_total", &labels),
+            stuffing_overhead_sent: Counter::new("ashv2_stuffing_overhead_sent_total", &labels),
+            stuffing_overhead_received: Counter::new(
+                "ashv2_stuffing_overhead_received_total",
+                &labels,
+            ),
+            ack_latency: Latency::new("ashv2_ack_latency_seconds", &labels),
+            window_occupancy: Gauge::new("ashv2_window_occupancy", &labels),
+            message_queue_depth: Gauge::new("ashv2_message_queue_depth", &labels),
+            response_queue_depth: Gauge::new("ashv2_response_queue_depth", &labels),
+            codes: Mutex::default(),
+            connections: watch::Sender::new(0),
+            labels,
+        }
+    }
+
+    /// Return the process-wide unique ID of the connection.
+    pub const fn connection_id(&self) -> u64 {
+        self.connection_id
+    }
+
+    /// Count a received `RST_ACK` frame with the given reset code.
+    pub fn count_reset(&self, code: Result<Code, u8>) {
+        self.labels
+            .count("ashv2_resets_total", "code", &code_label(code));
+        self.count_code(code, |codes| &mut codes.resets);
+    }
+
+    /// Count an established connection and notify its subscribers.
+    pub fn count_connection(&self) {
+        self.connections
+            .send_modify(|connections| *connections += 1);
+    }
+
+    /// Return the number of established connections.
+    pub fn connection(&self) -> u64 {
+        *self.connections.borrow()
+    }
+
+    /// Subscribe to the number of established connections.
+    pub fn connections(&self) -> watch::Receiver<u64> {
+        self.connections.subscribe()
+    }
+
+    /// Count a received `ERROR` frame with the given error code.
+    pub fn count_error(&self, code: Result<Code, u8>) {
+        self.labels
+            .count("ashv2_errors_total", "code", &code_label(code));
+        self.count_code(code, |codes| &mut codes.errors);
+    }
+
+    /// Return a snapshot of the current statistics.
+    pub fn snapshot(&self) -> Stats {
+        let codes = self.codes.lock().unwrap_or_else(PoisonError::into_inner);
+
+        Stats {
+            connection_id: self.connection_id(),
+            data_frames_sent: self.data_frames_sent.get(),
+            data_frames_received: self.data_frames_received.get(),
+            retransmissions: self.retransmissions.get(),
+            acks_sent: self.acks_sent.get(),
+            acks_received: self.acks_received.get(),
+            naks_sent: self.naks_sent.get(),
+            naks_received: self.naks_received.get(),
+            crc_failures: self.crc_failures.snapshot(),
+            out_of_sequence_frames: self.out_of_sequence_frames.get(),
+            decode_errors: self.decode_errors.get(),
+            substitutes: self.substitutes.get(),
+            cancels: self.cancels.get(),
+            rsts_sent: self.rsts_sent.get(),
+            resets: codes.resets.clone(),
+            errors: codes.errors.clone(),
+            unknown_codes: codes.unknown,
+            bytes_sent: self.bytes_sent.get(),
+            bytes_received: self.bytes_received.get(),
+            stuffing_overhead_sent: self.stuffing_overhead_sent.get(),
+            stuffing_overhead_received: self.stuffing_overhead_received.get(),
+            acked_frames: self.ack_latency.count(),
+            ack_latency_total: self.ack_latency.total(),
+            window_occupancy: self.window_occupancy.get(),
+            message_queue_depth: self.message_queue_depth.get(),
+            response_queue_depth: self.response_queue_depth.get(),
+        }
+    }
+
+    fn count_code<F>(&self, code: Result<Code, u8>, map: F)
+    where
+        F: FnOnce(&mut Codes) -> &mut BTreeMap<Code, u64>,
+    {
+        let mut codes = self.codes.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match code {
+            Ok(code) => *map(&mut codes).entry(code).or_default() += 1,
+            Err(_) => codes.unknown += 1,
+        }
+    }
+}
+
+impl Default for Counters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counters of received reset and error codes.
+#[derive(Debug, Default)]
+struct Codes {
+    resets: BTreeMap<Code, u64>,
+    errors: BTreeMap<Code, u64>,
+    unknown: u64,
+}
+
+/// Return the metrics label value for a reset or error code.
+fn code_label(code: Result<Code, u8>) -> String {
+    match code {
+        Ok(code) => format!("{code:?}"),
+        Err(code) => format!("{code:#04X}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Counters;
+    use crate::code::Code;
+
+    #[test]
+    fn snapshot_reflects_counters() {
+        let counters = Counters::default();
+        counters.data_frames_sent.increment();
+        counters.bytes_received.add(42);
+        counters.crc_failures.data.increment();
+        counters.crc_failures.nak.increment();
+        counters.count_reset(Ok(Code::PowerOn));
+        counters.count_reset(Ok(Code::PowerOn));
+        counters.count_error(Ok(Code::Assert));
+        counters.count_error(Err(0xFE));
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.data_frames_sent, 1);
+        assert_eq!(stats.bytes_received, 42);
+        assert_eq!(stats.crc_failures.data, 1);
+        assert_eq!(stats.crc_failures.total(), 2);
+        assert_eq!(stats.resets.get(&Code::PowerOn), Some(&2));
+        assert_eq!(stats.errors.get(&Code::Assert), Some(&1));
+        assert_eq!(stats.unknown_codes, 1);
+    }
+
+    #[test]
+    fn connections_have_distinct_ids() {
+        assert_ne!(
+            Counters::new().snapshot().connection_id,
+            Counters::new().snapshot().connection_id
+        );
+    }
+
+    #[test]
+    fn unpublished_counters_take_no_connection_id() {
+        assert_eq!(Counters::unpublished().snapshot().connection_id, 0);
+        assert_ne!(Counters::new().snapshot().connection_id, 0);
+    }
+}
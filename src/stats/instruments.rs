@@ -0,0 +1,119 @@
+//! Instruments backing the link statistics.
+//!
+//! With the `metrics` crate feature, every instrument also publishes its updates through the
+//! `metrics` facade, labeled with the connection it belongs to.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::Relaxed;
+use std::time::Duration;
+
+pub use self::facade::Labels;
+use self::facade::{CounterHandle, GaugeHandle, HistogramHandle};
+
+#[cfg(feature = "metrics")]
+mod facade;
+#[cfg(not(feature = "metrics"))]
+#[path = "instruments/noop.rs"]
+mod facade;
+
+/// A monotonically increasing counter.
+#[derive(Debug)]
+pub struct Counter {
+    value: AtomicU64,
+    handle: CounterHandle,
+}
+
+impl Counter {
+    /// Create a counter published under `name`.
+    pub fn new(name: &'static str, labels: &Labels) -> Self {
+        Self {
+            value: AtomicU64::new(0),
+            handle: CounterHandle::new(name, labels),
+        }
+    }
+
+    /// Increment the counter by one.
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    /// Increment the counter by `amount`.
+    pub fn add(&self, amount: usize) {
+        let amount = u64::try_from(amount).unwrap_or(u64::MAX);
+        self.value.fetch_add(amount, Relaxed);
+        self.handle.increment(amount);
+    }
+
+    /// Return the current value of the counter.
+    pub fn get(&self) -> u64 {
+        self.value.load(Relaxed)
+    }
+}
+
+/// A gauge holding the current value of a quantity.
+#[derive(Debug)]
+pub struct Gauge {
+    value: AtomicU64,
+    handle: GaugeHandle,
+}
+
+impl Gauge {
+    /// Create a gauge published under `name`.
+    pub fn new(name: &'static str, labels: &Labels) -> Self {
+        Self {
+            value: AtomicU64::new(0),
+            handle: GaugeHandle::new(name, labels),
+        }
+    }
+
+    /// Set the gauge to `value`.
+    pub fn set(&self, value: usize) {
+        let value = u64::try_from(value).unwrap_or(u64::MAX);
+        self.value.store(value, Relaxed);
+        self.handle.set(value);
+    }
+
+    /// Return the current value of the gauge.
+    pub fn get(&self) -> u64 {
+        self.value.load(Relaxed)
+    }
+}
+
+/// Accumulated latencies, published as a histogram in seconds.
+#[derive(Debug)]
+pub struct Latency {
+    count: AtomicU64,
+    total_micros: AtomicU64,
+    handle: HistogramHandle,
+}
+
+impl Latency {
+    /// Create a latency histogram published under `name`.
+    pub fn new(name: &'static str, labels: &Labels) -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            total_micros: AtomicU64::new(0),
+            handle: HistogramHandle::new(name, labels),
+        }
+    }
+
+    /// Record a latency.
+    pub fn record(&self, latency: Duration) {
+        self.count.fetch_add(1, Relaxed);
+        self.total_micros.fetch_add(
+            u64::try_from(latency.as_micros()).unwrap_or(u64::MAX),
+            Relaxed,
+        );
+        self.handle.record(latency);
+    }
+
+    /// Return the number of recorded latencies.
+    pub fn count(&self) -> u64 {
+        self.count.load(Relaxed)
+    }
+
+    /// Return the sum of all recorded latencies.
+    pub fn total(&self) -> Duration {
+        Duration::from_micros(self.total_micros.load(Relaxed))
+    }
+}
@@ -0,0 +1,211 @@
+//! Publishing of instruments through the `metrics` facade.
+
+use std::time::Duration;
+
+use metrics::Label;
+
+/// Labels identifying the metrics of one connection.
+///
+/// Unpublished labels register no metrics at all.
+#[derive(Clone, Debug)]
+pub struct Labels(Option<Vec<Label>>);
+
+impl Labels {
+    /// Create labels for the connection with the given ID.
+    pub fn new(connection_id: u64) -> Self {
+        Self(Some(vec![Label::new(
+            "connection",
+            connection_id.to_string(),
+        )]))
+    }
+
+    /// Create labels of instruments that are not published.
+    pub const fn unpublished() -> Self {
+        Self(None)
+    }
+
+    /// Return a copy of the labels with an additional label.
+    pub fn with(&self, key: &'static str, value: &'static str) -> Self {
+        Self(self.0.as_ref().map(|labels| {
+            let mut labels = labels.clone();
+            labels.push(Label::new(key, value));
+            labels
+        }))
+    }
+
+    /// Count an event under `name` with an additional label.
+    pub fn count(&self, name: &'static str, key: &'static str, value: &str) {
+        if let Some(labels) = &self.0 {
+            let mut labels = labels.clone();
+            labels.push(Label::new(key, value.to_owned()));
+            metrics::counter!(name, labels).increment(1);
+        }
+    }
+}
+
+/// Handle of a published counter.
+#[derive(Debug)]
+pub struct CounterHandle(metrics::Counter);
+
+impl CounterHandle {
+    pub fn new(name: &'static str, labels: &Labels) -> Self {
+        Self(
+            labels
+                .0
+                .clone()
+                .map_or_else(metrics::Counter::noop, |labels| {
+                    metrics::counter!(name, labels)
+                }),
+        )
+    }
+
+    pub fn increment(&self, amount: u64) {
+        self.0.increment(amount);
+    }
+}
+
+/// Handle of a published gauge.
+#[derive(Debug)]
+pub struct GaugeHandle(metrics::Gauge);
+
+impl GaugeHandle {
+    pub fn new(name: &'static str, labels: &Labels) -> Self {
+        Self(
+            labels
+                .0
+                .clone()
+                .map_or_else(metrics::Gauge::noop, |labels| metrics::gauge!(name, labels)),
+        )
+    }
+
+    pub fn set(&self, value: u64) {
+        self.0
+            .set(u32::try_from(value).map_or_else(|_| f64::from(u32::MAX), f64::from));
+    }
+}
+
+/// Handle of a published histogram.
+#[derive(Debug)]
+pub struct HistogramHandle(metrics::Histogram);
+
+impl HistogramHandle {
+    pub fn new(name: &'static str, labels: &Labels) -> Self {
+        Self(
+            labels
+                .0
+                .clone()
+                .map_or_else(metrics::Histogram::noop, |labels| {
+                    metrics::histogram!(name, labels)
+                }),
+        )
+    }
+
+    pub fn record(&self, duration: Duration) {
+        self.0.record(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering::Relaxed;
+    use std::sync::{Arc, Mutex};
+
+    use metrics::{
+        Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+        with_local_recorder,
+    };
+
+    use crate::stats::Counters;
+
+    /// Recorder that keeps the values of registered counters.
+    #[derive(Default)]
+    struct CounterRecorder {
+        counters: Mutex<Vec<(Key, Arc<AtomicU64>)>>,
+    }
+
+    impl CounterRecorder {
+        fn value(&self, name: &str, label: (&str, &str)) -> Option<u64> {
+            self.counters
+                .lock()
+                .expect("lock should not be poisoned")
+                .iter()
+                .filter(|(key, _)| key.name() == name)
+                .find(|(key, _)| {
+                    key.labels()
+                        .any(|other| (other.key(), other.value()) == label)
+                })
+                .map(|(_, value)| value.load(Relaxed))
+        }
+    }
+
+    impl Recorder for CounterRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            let value = Arc::new(AtomicU64::new(0));
+            self.counters
+                .lock()
+                .expect("lock should not be poisoned")
+                .push((key.clone(), value.clone()));
+            Counter::from_arc(value)
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    #[test]
+    fn counters_are_published_per_connection() {
+        let recorder = CounterRecorder::default();
+        let counters = with_local_recorder(&recorder, Counters::new);
+        let connection_id = counters.snapshot().connection_id.to_string();
+        counters.data_frames_sent.increment();
+        counters.bytes_sent.add(7);
+        counters.crc_failures.nak.increment();
+
+        let connection = ("connection", connection_id.as_str());
+        assert_eq!(
+            recorder.value("ashv2_data_frames_sent_total", connection),
+            Some(1)
+        );
+        assert_eq!(
+            recorder.value("ashv2_bytes_sent_total", connection),
+            Some(7)
+        );
+        assert_eq!(
+            recorder.value("ashv2_crc_failures_total", ("frame_type", "NAK")),
+            Some(1)
+        );
+        assert_eq!(
+            recorder.value("ashv2_crc_failures_total", ("frame_type", "ACK")),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn unpublished_counters_are_not_registered() {
+        let recorder = CounterRecorder::default();
+        let counters = with_local_recorder(&recorder, Counters::unpublished);
+        counters.data_frames_sent.increment();
+        counters.count_reset(Err(0xFE));
+
+        assert!(
+            recorder
+                .counters
+                .lock()
+                .expect("lock should not be poisoned")
+                .is_empty()
+        );
+        assert_eq!(counters.snapshot().data_frames_sent, 1);
+    }
+}
@@ -0,0 +1,61 @@
+//! No-op stand-ins for publishing instruments without the `metrics` feature.
+
+#![expect(clippy::missing_const_for_fn, clippy::unused_self)]
+
+use std::time::Duration;
+
+/// Labels identifying the metrics of one connection.
+#[derive(Clone, Debug)]
+pub struct Labels;
+
+impl Labels {
+    pub fn new(_connection_id: u64) -> Self {
+        Self
+    }
+
+    pub const fn unpublished() -> Self {
+        Self
+    }
+
+    pub fn with(&self, _key: &'static str, _value: &'static str) -> Self {
+        Self
+    }
+
+    pub fn count(&self, _name: &'static str, _key: &'static str, _value: &str) {}
+}
+
+/// Stand-in for a published counter.
+#[derive(Debug)]
+pub struct CounterHandle;
+
+impl CounterHandle {
+    pub fn new(_name: &'static str, _labels: &Labels) -> Self {
+        Self
+    }
+
+    pub fn increment(&self, _amount: u64) {}
+}
+
+/// Stand-in for a published gauge.
+#[derive(Debug)]
+pub struct GaugeHandle;
+
+impl GaugeHandle {
+    pub fn new(_name: &'static str, _labels: &Labels) -> Self {
+        Self
+    }
+
+    pub fn set(&self, _value: u64) {}
+}
+
+/// Stand-in for a published histogram.
+#[derive(Debug)]
+pub struct HistogramHandle;
+
+impl HistogramHandle {
+    pub fn new(_name: &'static str, _labels: &Labels) -> Self {
+        Self
+    }
+
+    pub fn record(&self, _duration: Duration) {}
+}
@@ -0,0 +1,442 @@
+//! TCP transport for network-attached NCPs.
+//!
+//! This module is available with the `tcp` crate feature. Many network coordinators expose the
+//! NCP's UART as a raw TCP socket (ser2net style). [`connect`] opens such a socket with
+//! `TCP_NODELAY` and keepalives enabled. [`Reconnecting`] wraps the connection and transparently
+//! reconnects with exponential backoff when the socket drops, and [`start`] starts the host actor
+//! on it.
+//!
+//! While the connection is re-established, reads wait for the new connection and writes wait
+//! until it is available. A write that fails on the dropped socket returns its error to the
+//! actor, which then resets the `ASHv2` connection.
+//!
+//! ```no_run
+//! use ashv2::tcp::{self, Settings};
+//! use tokio::sync::mpsc::channel;
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let (response_tx, response_rx) = channel(64);
+//! let (handle, futures) = tcp::start("192.168.1.10:6638", Settings::default(), response_tx).await?;
+//! tokio::spawn(futures.transmitter);
+//! tokio::spawn(futures.receiver);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::{Debug, Display, Formatter};
+use std::io::{self, ErrorKind};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+use log::{info, warn};
+use socket2::{SockRef, TcpKeepalive};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::Sender;
+use tokio::time::{Sleep, sleep};
+
+use crate::types::Payload;
+use crate::{Futures, Handle};
+
+/// Settings of a TCP connection to an NCP.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Settings {
+    /// Idle time after which keepalive probes are sent.
+    pub keepalive_time: Duration,
+
+    /// Interval between keepalive probes.
+    ///
+    /// This setting only takes effect on platforms that support it.
+    pub keepalive_interval: Duration,
+
+    /// Delay before the first reconnection attempt after a failed one.
+    pub initial_backoff: Duration,
+
+    /// Upper bound of the exponentially increasing delay between reconnection attempts.
+    pub max_backoff: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            keepalive_time: Duration::from_secs(10),
+            keepalive_interval: Duration::from_secs(5),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Connect to the NCP at `addr` and configure the socket.
+///
+/// # Errors
+///
+/// Returns an error if the connection could not be established or configured.
+pub async fn connect(addr: &str, settings: Settings) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect(addr).await?;
+    stream.set_nodelay(true)?;
+    let keepalive = TcpKeepalive::new().with_time(settings.keepalive_time);
+    #[cfg(any(
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "ios",
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "netbsd",
+        target_os = "windows",
+    ))]
+    let keepalive = keepalive.with_interval(settings.keepalive_interval);
+    SockRef::from(&stream).set_tcp_keepalive(&keepalive)?;
+    Ok(stream)
+}
+
+/// Connect to the NCP at `addr` and start the host actor on a [`Reconnecting`] connection.
+///
+/// See [`crate::start`] for how to drive the returned futures.
+///
+/// # Errors
+///
+/// Returns an error if the initial connection could not be established or configured.
+pub async fn start(
+    addr: &str,
+    settings: Settings,
+    response: Sender<Payload>,
+) -> io::Result<(
+    Handle,
+    Futures<impl Future<Output = ()> + Send + 'static, impl Future<Output = ()> + Send + 'static>,
+)> {
+    let (reader, writer) = tokio::io::split(Reconnecting::connect(addr, settings).await?);
+    Ok(crate::start(reader, writer, response))
+}
+
+/// A TCP connection that reconnects when the socket drops.
+pub struct Reconnecting {
+    addr: Arc<str>,
+    settings: Settings,
+    state: State,
+    backoff: Duration,
+    wakers: Arc<Wakers>,
+}
+
+impl Reconnecting {
+    /// Connect to the NCP at `addr`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the initial connection could not be established or configured.
+    pub async fn connect(addr: &str, settings: Settings) -> io::Result<Self> {
+        let stream = connect(addr, settings).await?;
+        Ok(Self {
+            addr: addr.into(),
+            settings,
+            state: State::Connected(stream),
+            backoff: settings.initial_backoff,
+            wakers: Arc::default(),
+        })
+    }
+
+    /// Poll until a connection is available.
+    ///
+    /// The caller's waker is registered for the given direction, so that both the reading and
+    /// the writing task are woken once the connection has been re-established.
+    fn poll_stream(&mut self, cx: &Context<'_>, direction: Slot) -> Poll<&mut TcpStream> {
+        self.wakers.register(direction, cx.waker());
+        let waker = Waker::from(self.wakers.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            match &mut self.state {
+                State::Connected(_) => break,
+                State::Waiting(delay) => {
+                    if delay.as_mut().poll(&mut cx).is_pending() {
+                        return Poll::Pending;
+                    }
+
+                    self.reconnect();
+                }
+                State::Connecting(connecting) => match connecting.as_mut().poll(&mut cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Ok(stream)) => {
+                        info!("Reconnected to {}.", self.addr);
+                        self.state = State::Connected(stream);
+                        self.backoff = self.settings.initial_backoff;
+                        self.wakers.wake_all();
+                    }
+                    Poll::Ready(Err(error)) => {
+                        warn!(
+                            "Failed to reconnect to {}: {error}. Retrying in {:?}.",
+                            self.addr, self.backoff
+                        );
+                        self.state = State::Waiting(Box::pin(sleep(self.backoff)));
+                        self.backoff = self
+                            .backoff
+                            .saturating_mul(2)
+                            .min(self.settings.max_backoff);
+                    }
+                },
+            }
+        }
+
+        match &mut self.state {
+            State::Connected(stream) => Poll::Ready(stream),
+            State::Waiting(_) | State::Connecting(_) => unreachable!("loop exits when connected"),
+        }
+    }
+
+    /// Drop the current connection and start reconnecting.
+    fn disconnect(&mut self, reason: &dyn Display) {
+        warn!(
+            "Connection to {} dropped: {reason}. Reconnecting.",
+            self.addr
+        );
+        self.reconnect();
+        self.wakers.wake_all();
+    }
+
+    fn reconnect(&mut self) {
+        let addr = self.addr.clone();
+        let settings = self.settings;
+        self.state = State::Connecting(Box::pin(async move { connect(&addr, settings).await }));
+    }
+}
+
+impl Debug for Reconnecting {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reconnecting")
+            .field("addr", &self.addr)
+            .field("settings", &self.settings)
+            .field("connected", &matches!(self.state, State::Connected(_)))
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsyncRead for Reconnecting {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            let Poll::Ready(stream) = this.poll_stream(cx, Slot::Read) else {
+                return Poll::Pending;
+            };
+            let filled = buf.filled().len();
+
+            match Pin::new(stream).poll_read(cx, buf) {
+                Poll::Ready(Ok(())) if buf.filled().len() == filled && buf.remaining() > 0 => {
+                    this.disconnect(&"end of stream");
+                }
+                Poll::Ready(Err(error)) => this.disconnect(&error),
+                poll => return poll,
+            }
+        }
+    }
+}
+
+impl AsyncWrite for Reconnecting {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        let Poll::Ready(stream) = this.poll_stream(cx, Slot::Write) else {
+            return Poll::Pending;
+        };
+
+        match Pin::new(stream).poll_write(cx, buf) {
+            Poll::Ready(Ok(0)) if !buf.is_empty() => {
+                this.disconnect(&"zero-length write");
+                Poll::Ready(Err(ErrorKind::WriteZero.into()))
+            }
+            Poll::Ready(Err(error)) => {
+                this.disconnect(&error);
+                Poll::Ready(Err(error))
+            }
+            poll => poll,
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().state {
+            State::Connected(stream) => Pin::new(stream).poll_flush(cx),
+            State::Waiting(_) | State::Connecting(_) => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().state {
+            State::Connected(stream) => Pin::new(stream).poll_shutdown(cx),
+            State::Waiting(_) | State::Connecting(_) => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+/// Connection state of a [`Reconnecting`] stream.
+enum State {
+    Connected(TcpStream),
+    Waiting(Pin<Box<Sleep>>),
+    Connecting(Pin<Box<dyn Future<Output = io::Result<TcpStream>> + Send + Sync>>),
+}
+
+/// The direction in which a task waits for the connection.
+#[derive(Clone, Copy, Debug)]
+enum Slot {
+    Read,
+    Write,
+}
+
+/// Wakers of the reading and writing task.
+///
+/// The halves of a split stream are usually polled from different tasks, whereas the pending
+/// reconnection only retains the waker it was last polled with. This wakes both tasks instead.
+#[derive(Debug, Default)]
+struct Wakers {
+    read: Mutex<Option<Waker>>,
+    write: Mutex<Option<Waker>>,
+}
+
+impl Wakers {
+    fn register(&self, slot: Slot, waker: &Waker) {
+        let slot = match slot {
+            Slot::Read => &self.read,
+            Slot::Write => &self.write,
+        };
+        slot.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .replace(waker.clone());
+    }
+
+    fn wake_all(&self) {
+        for slot in [&self.read, &self.write] {
+            let waker = slot.lock().unwrap_or_else(PoisonError::into_inner).take();
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Wake for Wakers {
+    fn wake(self: Arc<Self>) {
+        self.wake_all();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, split};
+    use tokio::net::TcpListener;
+    use tokio::runtime::{Builder, Runtime};
+    use tokio::sync::mpsc::channel;
+
+    use super::{Reconnecting, Settings, start};
+    use crate::code::Code;
+    use crate::ncp;
+
+    fn runtime() -> Runtime {
+        Builder::new_current_thread()
+            .enable_io()
+            .enable_time()
+            .build()
+            .expect("runtime should build")
+    }
+
+    #[test]
+    fn start_talks_to_ncp_over_tcp() {
+        runtime().block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("listener should bind");
+            let addr = listener
+                .local_addr()
+                .expect("listener should have an address");
+            let (response_tx, _response_rx) = channel(8);
+            let (handle, futures) = start(&addr.to_string(), Settings::default(), response_tx)
+                .await
+                .expect("host should connect");
+            let (socket, _) = listener.accept().await.expect("host should connect");
+            let (ncp_tx, mut ncp_rx) = channel(8);
+            let (reader, writer) = split(socket);
+            let (_ncp, ncp_futures) = ncp::start(reader, writer, ncp_tx, Code::PowerOn);
+            let tasks = [
+                tokio::spawn(ncp_futures.transmitter),
+                tokio::spawn(ncp_futures.receiver),
+                tokio::spawn(futures.transmitter),
+                tokio::spawn(futures.receiver),
+            ];
+
+            let request = [0x00, 0x00, 0x00, 0x02].into_iter().collect();
+            handle.send(request).await.expect("request should be sent");
+            let payload = ncp_rx.recv().await.expect("NCP should receive the request");
+            assert_eq!(payload.as_slice(), [0x00, 0x00, 0x00, 0x02]);
+
+            for task in tasks {
+                task.abort();
+            }
+        });
+    }
+
+    #[test]
+    fn reconnects_when_socket_drops() {
+        runtime().block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("listener should bind");
+            let addr = listener
+                .local_addr()
+                .expect("listener should have an address");
+            let mut stream = Reconnecting::connect(&addr.to_string(), Settings::default())
+                .await
+                .expect("client should connect");
+            let mut buffer = [0; 1];
+
+            let (mut socket, _) = listener.accept().await.expect("client should connect");
+            socket
+                .write_all(&[0x01])
+                .await
+                .expect("server should write");
+            stream
+                .read_exact(&mut buffer)
+                .await
+                .expect("client should read");
+            assert_eq!(buffer, [0x01]);
+            drop(socket);
+
+            let server = tokio::spawn(async move {
+                let (mut socket, _) = listener.accept().await.expect("client should reconnect");
+                socket
+                    .write_all(&[0x02])
+                    .await
+                    .expect("server should write");
+                socket
+            });
+            stream
+                .read_exact(&mut buffer)
+                .await
+                .expect("client should read after reconnecting");
+            assert_eq!(buffer, [0x02]);
+
+            let mut socket = server.await.expect("server should accept");
+            stream
+                .write_all(&[0x03])
+                .await
+                .expect("client should write");
+            socket
+                .read_exact(&mut buffer)
+                .await
+                .expect("server should read");
+            assert_eq!(buffer, [0x03]);
+        });
+    }
+}
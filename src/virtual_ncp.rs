@@ -0,0 +1,194 @@
+//! In-process virtual NCP for integration testing.
+//!
+//! This module is available with the `virtual-ncp` crate feature. [`start`] creates an NCP-side
+//! actor connected to an in-memory [`tokio::io::duplex`] stream and returns the host side of that
+//! stream as a [`Transport`], which can be passed to [`crate::start`]. The virtual NCP completes
+//! the reset handshake and acknowledges `DATA` frames like a real NCP.
+//!
+//! Tests script inbound `DATA` payloads with [`VirtualNcp::send`] and assert outbound payloads
+//! with [`VirtualNcp::receive`] or [`VirtualNcp::exchange`].
+//!
+//! ```
+//! use ashv2::Code;
+//! use ashv2::virtual_ncp;
+//! use tokio::sync::mpsc::channel;
+//!
+//! # async fn run() {
+//! let (mut ncp, transport, ncp_futures) = virtual_ncp::start(Code::PowerOn);
+//! let (response_tx, mut response_rx) = channel(8);
+//! let (handle, futures) = ashv2::start(transport.reader, transport.writer, response_tx);
+//! tokio::spawn(ncp_futures.transmitter);
+//! tokio::spawn(ncp_futures.receiver);
+//! tokio::spawn(futures.transmitter);
+//! tokio::spawn(futures.receiver);
+//!
+//! let request = [0x00, 0x00, 0x00, 0x02].into_iter().collect();
+//! handle.send(request).await.expect("request should be sent");
+//! let response = [0x00, 0x80, 0x00, 0x02, 0x02, 0x11, 0x30].into_iter().collect();
+//! let request = ncp.exchange(response).await.expect("host should send a request");
+//! assert_eq!(request.as_slice(), [0x00, 0x00, 0x00, 0x02]);
+//! assert!(response_rx.recv().await.is_some());
+//! # }
+//! ```
+
+use tokio::io::{DuplexStream, ReadHalf, WriteHalf, duplex, split};
+use tokio::sync::mpsc::{Receiver, channel};
+
+use crate::code::Code;
+use crate::error::Error;
+use crate::types::{MAX_FRAME_SIZE, Payload};
+use crate::{Futures, ncp};
+
+/// Capacity of the in-memory stream in each direction.
+const STREAM_BUFFER_SIZE: usize = MAX_FRAME_SIZE * 16;
+
+/// Capacity of the channel of payloads received from the host.
+const CHANNEL_SIZE: usize = 64;
+
+/// Host side of the in-memory transport connected to a [`VirtualNcp`].
+#[derive(Debug)]
+pub struct Transport {
+    /// Reader to pass to [`crate::start`].
+    pub reader: ReadHalf<DuplexStream>,
+
+    /// Writer to pass to [`crate::start`].
+    pub writer: WriteHalf<DuplexStream>,
+}
+
+/// Virtual NCP connected to a host through an in-memory stream.
+#[derive(Debug)]
+pub struct VirtualNcp {
+    handle: ncp::Handle,
+    payloads: Receiver<Payload>,
+}
+
+impl VirtualNcp {
+    /// Send a `DATA` payload to the host.
+    ///
+    /// Payloads sent before the host has reset the connection remain queued.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the NCP's actor futures are no longer running or the payload could not
+    /// be written.
+    pub async fn send(&self, payload: Payload) -> Result<(), Error> {
+        self.handle.send(payload).await
+    }
+
+    /// Send an `ERROR` frame with the given code to the host.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the NCP's actor futures are no longer running.
+    pub async fn send_error(&self, code: Code) -> Result<(), Error> {
+        self.handle.send_error(code).await
+    }
+
+    /// Receive the next `DATA` payload sent by the host.
+    ///
+    /// Returns `None` if the NCP's actor futures have terminated.
+    pub async fn receive(&mut self) -> Option<Payload> {
+        self.payloads.recv().await
+    }
+
+    /// Receive the next `DATA` payload sent by the host and answer it with `response`.
+    ///
+    /// Returns the received payload or `None` if the NCP's actor futures have terminated or the
+    /// response could not be sent.
+    pub async fn exchange(&mut self, response: Payload) -> Option<Payload> {
+        let request = self.receive().await?;
+        self.send(response).await.ok()?;
+        Some(request)
+    }
+}
+
+/// Create a virtual NCP that answers `RST` frames with `RST_ACK` frames carrying `reset_code`.
+///
+/// Returns the [`VirtualNcp`], the host side of the in-memory transport and the NCP's actor
+/// [`Futures`], which the caller must spawn or otherwise poll alongside the host's futures.
+#[must_use]
+pub fn start(
+    reset_code: Code,
+) -> (
+    VirtualNcp,
+    Transport,
+    Futures<impl Future<Output = ()> + Send + 'static, impl Future<Output = ()> + Send + 'static>,
+) {
+    let (host_stream, ncp_stream) = duplex(STREAM_BUFFER_SIZE);
+    let (reader, writer) = split(host_stream);
+    let (ncp_reader, ncp_writer) = split(ncp_stream);
+    let (response, payloads) = channel(CHANNEL_SIZE);
+    let (handle, futures) = ncp::start(ncp_reader, ncp_writer, response, reset_code);
+    (
+        VirtualNcp { handle, payloads },
+        Transport { reader, writer },
+        futures,
+    )
+}
+
+/// Start a host actor connected to a virtual NCP and spawn the actor futures of both.
+///
+/// Returns the host's handle, the receiving end of its response channel and the virtual NCP.
+#[cfg(test)]
+pub(crate) fn connect() -> (crate::Handle, Receiver<Payload>, VirtualNcp) {
+    let (ncp, transport, ncp_futures) = start(Code::PowerOn);
+    let (response, payloads) = channel(CHANNEL_SIZE);
+    let (handle, futures) = crate::start(transport.reader, transport.writer, response);
+    tokio::spawn(ncp_futures.transmitter);
+    tokio::spawn(ncp_futures.receiver);
+    tokio::spawn(futures.transmitter);
+    tokio::spawn(futures.receiver);
+    (handle, payloads, ncp)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::runtime::Builder;
+    use tokio::sync::mpsc::channel;
+
+    use crate::code::Code;
+    use crate::types::Payload;
+
+    const CHANNEL_SIZE: usize = 8;
+
+    #[test]
+    fn host_talks_to_virtual_ncp() {
+        Builder::new_current_thread()
+            .build()
+            .expect("runtime should build")
+            .block_on(async {
+                let (mut ncp, transport, ncp_futures) = super::start(Code::PowerOn);
+                let (response_tx, mut response_rx) = channel(CHANNEL_SIZE);
+                let (handle, futures) =
+                    crate::start(transport.reader, transport.writer, response_tx);
+                tokio::spawn(ncp_futures.transmitter);
+                tokio::spawn(ncp_futures.receiver);
+                tokio::spawn(futures.transmitter);
+                tokio::spawn(futures.receiver);
+
+                // Payloads pass in both directions, also exceeding the sliding window size.
+                for index in 0..16 {
+                    let request: Payload = [index, 0x00, 0x00, 0x02].into_iter().collect();
+                    let response: Payload = [index, 0x80, 0x00, 0x02].into_iter().collect();
+                    handle
+                        .send(request.clone())
+                        .await
+                        .expect("host should send");
+                    assert_eq!(ncp.exchange(response.clone()).await, Some(request));
+                    assert_eq!(response_rx.recv().await, Some(response));
+                }
+
+                let callback: Payload = [0x10, 0x90, 0x00, 0x19].into_iter().collect();
+                ncp.send(callback.clone()).await.expect("NCP should send");
+                assert_eq!(response_rx.recv().await, Some(callback));
+
+                let stats = handle.stats();
+                assert_eq!(stats.data_frames_sent, 16);
+                assert_eq!(stats.data_frames_received, 17);
+                assert_eq!(stats.resets.get(&Code::PowerOn), Some(&1));
+                assert_eq!(stats.crc_failures.total(), 0);
+                assert!(stats.bytes_sent > 0);
+                assert!(stats.bytes_received > 0);
+            });
+    }
+}